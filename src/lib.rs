@@ -85,9 +85,21 @@
 // Note: Utf-8 Can encode reverse text (right to left), probably downwards, etc.  
 //       This crate ignores ALL THAT.
 //
-use unicode_segmentation::{GraphemeCursor, UnicodeSegmentation};
+use std::collections::{BTreeMap, BTreeSet};
+use std::hash::{Hash, Hasher};
+use std::ops::Index;
+use unicode_segmentation::{GraphemeCursor, GraphemeIncomplete, UnicodeSegmentation};
 use unicode_width::UnicodeWidthStr;
 
+#[cfg(feature = "std")]
+use std::io::{self, Read};
+#[cfg(feature = "width-override")]
+use std::sync::RwLock;
+#[cfg(feature = "width-override")]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "normalize")]
+use unicode_normalization::UnicodeNormalization;
+
 //  Notes on Graphemes
 //	It's complicated...  and with the way the world works, it keeps getting more complicated.
 //	One comic suggested that the unicode foundation has the job of trying to direct a flooding
@@ -131,481 +143,3684 @@ use unicode_width::UnicodeWidthStr;
 //  Note:
 //  Challenge Case - /t is reported as a single character, real width depends on column, and tabstops!!!
 
+// Width overrides, see register_width_override
+//
+// HAS_WIDTH_OVERRIDES lets cluster_width skip the lock entirely until the
+// first override is registered.
+#[cfg(feature = "width-override")]
+static WIDTH_OVERRIDES: RwLock<BTreeMap<char, usize>> = RwLock::new(BTreeMap::new());
+#[cfg(feature = "width-override")]
+static HAS_WIDTH_OVERRIDES: AtomicBool = AtomicBool::new(false);
 
-/// Return the grapheme at the given string idx
+// Registered override width for the grapheme's base scalar, if any
+#[cfg(feature = "width-override")]
+fn width_override(grapheme: &str) -> Option<usize> {
+    if !HAS_WIDTH_OVERRIDES.load(Ordering::Acquire) {
+        return None;
+    }
+    let table = WIDTH_OVERRIDES.read().unwrap_or_else(|e| e.into_inner());
+    grapheme
+        .chars()
+        .next()
+        .and_then(|ch| table.get(&ch).copied())
+}
+
+#[cfg(not(feature = "width-override"))]
+fn width_override(_grapheme: &str) -> Option<usize> {
+    None
+}
+
+// Visual column width of a single grapheme
+//
+// Every width in this crate goes through here, so registered width
+// overrides apply everywhere.
+fn cluster_width(grapheme: &str) -> usize {
+    width_override(grapheme).unwrap_or_else(|| grapheme.width())
+}
+
+
+// Append fill until it covers columns, never overshooting
+//
+// A width 2 fill only goes in whole, so an odd leftover column stays
+// empty.  A zero width fill is counted as 1 column so this terminates.
+fn push_fill(out: &mut String, columns: usize, fill: char) {
+    let fill_width = cluster_width(fill.encode_utf8(&mut [0; 4])).max(1);
+    out.extend(std::iter::repeat_n(fill, columns / fill_width));
+}
+
+/// Align Numbers on their Decimal Point
+///
+/// The integer parts (before the first "." grapheme) are padded on the
+/// left to the widest integer part, then the fractional parts (from the
+/// "." on) are padded on the right to the widest fractional part.  Items
+/// without a "." are aligned as whole integers.  All padding is by
+/// display width.
 ///
 /// ```rust
 /// use grapheme_utils::*;
-/// 
+///
 /// fn main() {
-///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
-/// 
-///     println!("grapheme_at_idx {}", grapheme_at_idx(&st, 18)); // Prints H
+///     for line in align_decimal(&["1.5", "22.25", "3"], ' ') {
+///         println!("[{}]", line);
+///     }
+///     // Prints
+///     // [ 1.5 ]
+///     // [22.25]
+///     // [ 3   ]
 /// }
 /// ```
-pub fn grapheme_at_idx(st: &str, idx: usize) -> &str {
-    let pos = grapheme_idx_at_idx(&st, idx);
-    &st[pos..pos + st[pos..].graphemes(true).next().unwrap_or("").len()]
+pub fn align_decimal(items: &[&str], fill: char) -> Vec<String> {
+    let split: Vec<(&str, &str)> = items
+        .iter()
+        .map(|item| {
+            let dot = item
+                .grapheme_indices(true)
+                .find(|(_, grapheme)| *grapheme == ".")
+                .map_or(item.len(), |(idx, _)| idx);
+            item.split_at(dot)
+        })
+        .collect();
+    let int_width = split
+        .iter()
+        .map(|(int, _)| string_width(int))
+        .max()
+        .unwrap_or(0);
+    let frac_width = split
+        .iter()
+        .map(|(_, frac)| string_width(frac))
+        .max()
+        .unwrap_or(0);
+
+    split
+        .iter()
+        .map(|(int, frac)| {
+            let mut out = String::new();
+            push_fill(&mut out, int_width - string_width(int), fill);
+            out.push_str(int);
+            out.push_str(frac);
+            push_fill(&mut out, frac_width - string_width(frac), fill);
+            out
+        })
+        .collect()
 }
 
-/// Grapheme length in Bytes
+/// Byte Length of the Leading Printable Ascii Run
+///
+/// Every byte of the prefix is a 1 column grapheme (width == byte count),
+/// so a renderer can blit it directly and only do the unicode work on
+/// the rest.
+///
+/// Note: An ascii letter followed by a combining mark ("e\u{301}") is
+///       left out of the prefix, it belongs to the grapheme after it.
 ///
 /// ```rust
 /// use grapheme_utils::*;
-/// 
+///
 /// fn main() {
-///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
-/// 
-///     let num = 7; // Anything between 6 and 17 inclusive
-/// 
-///     // Grapheme utf8 byte count or length in bytes
-///     println!("grapheme_len {}", grapheme_len(&st, num)); // Prints 12 (न्दी uses 12 utf8 bytes)
-///     println!("grapheme_len {}", grapheme_len(&st, 18)); // Prints 1 (H uses 1 utf8 byte)
+///     println!("ascii_prefix_len {}", ascii_prefix_len("abc你好")); // Prints 3
+///     println!("ascii_prefix_len {}", ascii_prefix_len("abc\u{301}")); // Prints 2
 /// }
 /// ```
-pub fn grapheme_len(st: &str, idx: usize) -> usize {
-    let pos = grapheme_idx_at_idx(&st, idx);
-    st[pos..].graphemes(true).next().unwrap_or("").len()
+pub fn ascii_prefix_len(st: &str) -> usize {
+    let end = st
+        .bytes()
+        .position(|b| !(0x20..=0x7e).contains(&b))
+        .unwrap_or(st.len());
+    grapheme_idx_at_idx(st, end)
 }
 
-/// Starting idx of Grapheme
+/// Average Grapheme Width
 ///
-/// This returns the starting index for the grapheme given
-/// a string byte idx
+/// string_width divided by num_graphemes, 0.0 for an empty string.
+/// Close to 1.0 means mostly narrow (ascii like) text, close to 2.0
+/// mostly wide (CJK or emoji).
 ///
-/// Note: This is an example wasteful function, but it
-///       usually returns the same index you're providing.
-///       
-///   
 /// ```rust
 /// use grapheme_utils::*;
-/// 
+///
 /// fn main() {
 ///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
-/// 
-///     println!("grapheme_idx_at_idx {}", grapheme_idx_at_idx(&st, 18)); // Prints 18
+///
+///     println!("avg_grapheme_width {}", avg_grapheme_width(&st)); // Prints 1.5
+///     println!("avg_grapheme_width {}", avg_grapheme_width("你好")); // Prints 2
 /// }
 /// ```
-pub fn grapheme_idx_at_idx(st: &str, idx: usize) -> usize {
-    if idx == 0 {
-        return 0;
-    }
-    let mut pos = idx;
-
-    if idx >= st.len() {
-        return st.len();
+pub fn avg_grapheme_width(st: &str) -> f64 {
+    let (count, width) = st.graphemes(true).fold((0, 0), |(count, width), grapheme| {
+        (count + 1, width + cluster_width(grapheme))
+    });
+    if count == 0 {
+        return 0.0;
     }
-    let mut cursor = GraphemeCursor::new(idx, st.len(), true);
+    width as f64 / count as f64
+}
 
-    loop {
-        while pos > 0 && (st.as_bytes()[pos] & 0xc0) == 0x80 {
-            pos -= 1;
-        }
-        cursor.set_cursor(pos);
-        if cursor.is_boundary(st, 0).unwrap_or(false) {
-            break;
-        }
-        pos -= 1;
-    }
-    pos
+// Does ch attach to the grapheme before it (Extend, SpacingMark, ZWJ)?
+//
+// unicode_segmentation doesn't expose the grapheme categories, so ask it
+// instead: if "a" followed by ch is still one grapheme, ch is a combining
+// kind of scalar.
+fn is_extending(ch: char) -> bool {
+    let mut buf = [0u8; 5];
+    buf[0] = b'a';
+    let len = 1 + ch.encode_utf8(&mut buf[1..]).len();
+    let st = std::str::from_utf8(&buf[..len]).unwrap_or("");
+    st.graphemes(true).nth(1).is_none()
 }
 
-/// Return the grapheme starting at or after the given byte index in a string.
+/// Number of Graphemes with a Base Character
+///
+/// Same as num_graphemes, except a defective grapheme made only of
+/// combining marks (like a string starting with U+301) isn't counted.
+/// This only differs from num_graphemes for malformed input.
 ///
 /// ```rust
 /// use grapheme_utils::*;
-/// 
+///
 /// fn main() {
-///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
-/// 
-///     // Grapheme Visual Column Width
-///     println!("grapheme_width_at_idx {}", grapheme_width_at_idx(&st, 18)); // Prints 1 (H is 1 column wide)
+///     let st = "\u{301}abé".to_string();
 ///
-///     let num = 7; // Anything between 6 and 17 inclusive
-///     println!(
-///         "grapheme_width_at_idx {}",
-///         grapheme_width_at_idx(&st, num)
-///     ); // Prints 3 (न्दी is 3 columns wide)
+///     println!("num_graphemes {}", num_graphemes(&st)); // Prints 4
+///     println!("base_char_count {}", base_char_count(&st)); // Prints 3
 /// }
 /// ```
-pub fn grapheme_width_at_idx(st: &str, idx: usize) -> usize {
-    let pos = grapheme_idx_at_idx(&st, idx);
-    st[pos..].graphemes(true).next().unwrap_or("").width()
+pub fn base_char_count(st: &str) -> usize {
+    st.graphemes(true)
+        .filter(|g| g.chars().next().is_some_and(|ch| !is_extending(ch)))
+        .count()
 }
 
-/// Next Grapheme from Current Index
+/// Most Columns of Items that Fit in total_width, for grid_layout
+///
+/// Every cell is as wide as the widest item (by display width), with gap
+/// columns between cells.  Never more columns than items (so 0 for no
+/// items), and at least 1 even when a single item doesn't fit.
 ///
 /// ```rust
 /// use grapheme_utils::*;
-/// 
+///
 /// fn main() {
-///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
-/// 
-///     println!("next_grapheme_from_idx {}", next_grapheme_from_idx(&st, 18)); // Prints 🧑
+///     let items = ["apple", "pear", "你好", "kiwi", "plum", "fig", "lime", "date"];
+///
+///     println!("{}", best_column_count(&items, 40, 2)); // Prints 6, 6 x 5 + 5 x 2 = 40
 /// }
 /// ```
-pub fn next_grapheme_from_idx(st: &str, idx: usize) -> &str {
-    let st_len = st.len();
-    if idx >= st_len {
-        return "";
-    }
-    let pos = next_grapheme_idx_from_idx(&st, idx);
-    st[pos..].graphemes(true).next().unwrap_or("")
+pub fn best_column_count(items: &[&str], total_width: usize, gap: usize) -> usize {
+    let cell_width = items
+        .iter()
+        .map(|item| string_width(item))
+        .max()
+        .unwrap_or(0);
+    let cols = (total_width + gap)
+        .checked_div(cell_width + gap)
+        .unwrap_or(items.len());
+    cols.max(1).min(items.len())
 }
 
-/// Byte Index of the Next Extended Grapheme from Current Index
-///
-/// NOTE: This can return the st.len(), meaning an illegal index
-///       if this is the last Grapheme in the string!
+/// Grapheme Boundaries of a Short String as a Bitmask
 ///
-/// Note: In testing, The Rust library currently divides
-///       some characters that should be singular "🧑🌾"
-///
-/// Note: This code is inefficient...  small, but inefficient.
+/// Bit i is set when byte index i starts a grapheme, so a boundary test
+/// in a tight loop is a single bit op: `mask & (1 << idx) != 0`.
+/// The end of the string (st.len()) isn't included.
 ///
-/// Note: This function has been modified to be panic proof.
-///       The underlying library can panic:
-///       unicode-segmentation-1.12.0/src/grapheme.rs:787:29:
+/// Note: Only strings up to 64 bytes fit, longer strings return 0.
+///       Any other non-empty string always has bit 0 set, so 0 can't be
+///       mistaken for a real mask.
 ///
 /// ```rust
 /// use grapheme_utils::*;
-/// 
+///
 /// fn main() {
-///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
-/// 
-///     println!("next_grapheme_from_idx {}", next_grapheme_from_idx(&st, 18)); // Prints 🧑
+///     let st = "a\u{e9}b".to_string();
+///
+///     println!("{:b}", boundary_bitmask(&st)); // Prints 1011, boundaries at 0, 1 and 3
 /// }
 /// ```
-// Note: next_boundary is written to only require
-//       a short chunk of the string instead of the
-//       whole thing:
-//           next_boundary(st[beg..beg+ch.len_utf8+etc], beg)
-//
-//       next_boundary can then send an error back saying if
-//       it needs more (GraphemeIncomplete::NextChunk)
-//
-//       It may or may not to land on exact utf8 char
-//       boundaries, but it's really hard for prev_grapheme_idx
-//       where you need to know the exact info we're wanting.
-//
-pub fn next_grapheme_idx_from_idx(st: &str, idx: usize) -> usize {
-    let st_len = st.len();
-    if idx >= st_len {
-        return st_len;
-    }
-    let mut pos = idx;
-    while pos > 0 && (st.as_bytes()[pos] & 0xc0) == 0x80 {
-        pos -= 1;
+pub fn boundary_bitmask(st: &str) -> u64 {
+    if st.len() > 64 {
+        return 0;
     }
-    let mut cursor = GraphemeCursor::new(pos, st_len, true);
-    cursor
-        .next_boundary(st, 0)
-        .ok()
-        .flatten()
-        .unwrap_or_else(|| st_len)
+    st.grapheme_indices(true)
+        .fold(0, |mask, (idx, _)| mask | (1 << idx))
 }
 
-/// nth Grapheme
+/// Iterate (start, end) Byte Ranges of Every Grapheme
 ///
-/// Note, this will return the st.len() index if it would be
-///       past the end of the string even if the string
-///       is empty.
+/// Each pair is a pair of neighboring boundaries, so a rendering loop gets
+/// a grapheme's [start, end) directly.  Lazy, nothing is collected.
 ///
 /// ```rust
 /// use grapheme_utils::*;
-/// 
+///
 /// fn main() {
 ///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
-/// 
-///     println!("nth_grapheme {}", nth_grapheme(&st, 1)); // Prints न्दी, the 2nd byte in the string, base 0
-///     println!("nth_grapheme {}", nth_grapheme(&st, 2)); // Prints H, the 3rd bytes in the string, base 0
+///
+///     println!("{:?}", boundary_pairs(&st).next()); // Prints Some((0, 6))
+///     println!("{:?}", boundary_pairs(&st).last()); // Prints Some((44, 47))
 /// }
 /// ```
-// UUGH - Full Iter to nth!
-//
-pub fn nth_grapheme(st: &str, nth: usize) -> &str {
-    UnicodeSegmentation::grapheme_indices(st, true)
-        .nth(nth)
-        .map(|(_, g)| g)
-        .unwrap_or_else(|| "")
+pub fn boundary_pairs(st: &str) -> impl Iterator<Item = (usize, usize)> {
+    st.grapheme_indices(true)
+        .map(|(idx, grapheme)| (idx, idx + grapheme.len()))
 }
 
-/// nth Grapheme Index from nth
+/// Byte Indexes Where Wrapping at width Columns Would Break
 ///
-/// Note, this will return the st.len() index if it would be
-///       past the end of the string even if the string
-///       is empty.
+/// Greedy grapheme wrapping: a row is broken before the first grapheme
+/// that would make it wider than width.  Each break is the byte index the
+/// next row starts at, 0 and st.len() are never included.
+///
+/// Note: A grapheme wider than width gets a row of its own.
 ///
 /// ```rust
 /// use grapheme_utils::*;
-/// 
+///
 /// fn main() {
 ///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
-/// 
-///     println!("nth_grapheme_idx {}", nth_grapheme_idx(&st, 1)); // Prints 6 (index 6)
-///     println!("nth_grapheme_idx {}", nth_grapheme_idx(&st, 2)); // Prints 18 (index 18)
+///
+///     println!("{:?}", break_points_for_width(&st, 5)); // Prints [18, 27, 41]
+///     // The rows are हिन्दी, H🧑🌾, e‘︀o‘︁ and réé
 /// }
 /// ```
-// Uugh - Full Iter!
-//
-pub fn nth_grapheme_idx(st: &str, nth: usize) -> usize {
-    UnicodeSegmentation::grapheme_indices(st, true)
-        .nth(nth)
-        .map(|(idx, _)| idx)
-        .unwrap_or_else(|| st.len())
+pub fn break_points_for_width(st: &str, width: usize) -> Vec<usize> {
+    let mut breaks = Vec::new();
+    let mut row_start = 0;
+    let mut row_width = 0;
+    for (idx, grapheme) in st.grapheme_indices(true) {
+        let w = cluster_width(grapheme);
+        if idx > row_start && row_width + w > width {
+            breaks.push(idx);
+            row_start = idx;
+            row_width = 0;
+        }
+        row_width += w;
+    }
+    breaks
 }
 
-/// nth Grapheme Width
+/// Split into Chunks of at most max_bytes, on Grapheme Boundaries
+///
+/// Note: A single grapheme longer than max_bytes is never split, it gets
+///       a chunk of its own which exceeds the budget.  For example the 12
+///       byte न्दी with a budget of 8.
 ///
 /// ```rust
 /// use grapheme_utils::*;
-/// 
+///
 /// fn main() {
 ///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
-/// 
-///     println!("nth grapheme_width {}", nth_grapheme_width(&st, 1)); // Prints 3
-///     println!("nth grapheme_width {}", nth_grapheme_width(&st, 2)); // Prints 1
+///
+///     println!("{:?}", byte_budget_chunks(&st, 10));
+///     // Prints ["हि", "न्दी", "H🧑🌾e", "‘︀o", "‘︁ré", "é"]
 /// }
 /// ```
-pub fn nth_grapheme_width(st: &str, nth: usize) -> usize {
-    UnicodeSegmentation::grapheme_indices(st, true)
-        .nth(nth)
-        .map(|(_, g)| g)
-        .unwrap_or_else(|| "")
-        .width()
+pub fn byte_budget_chunks(st: &str, max_bytes: usize) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    for (idx, grapheme) in st.grapheme_indices(true) {
+        if idx > start && idx + grapheme.len() - start > max_bytes {
+            chunks.push(&st[start..idx]);
+            start = idx;
+        }
+    }
+    if start < st.len() {
+        chunks.push(&st[start..]);
+    }
+    chunks
 }
 
-/// Num Graphemes In &str
+/// Grapheme Numbers Covered by a Byte Range, like a Regex Match
+///
+/// Returns (first, end) grapheme numbers, end exclusive, for highlighting
+/// a byte based match by whole graphemes.  A start inside a grapheme snaps
+/// back to its start and an end inside a grapheme takes in the whole
+/// grapheme, so a partly matched grapheme is highlighted.  Out of range
+/// indexes are clamped and end is never before start.
 ///
-/// Note, this will return the st.len() index if it would be
-///       past the end of the string even if the string
-///       is empty.
 /// ```rust
 /// use grapheme_utils::*;
-/// 
+///
 /// fn main() {
 ///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
-/// 
-///     println!("num_graphemes {}", num_graphemes(&st)); // Prints 12, the string has 12 grapheme clusters total
+///
+///     println!("{:?}", byte_range_to_grapheme_range(&st, 6, 18)); // Prints (1, 2), न्दी
+///     println!("{:?}", byte_range_to_grapheme_range(&st, 7, 20)); // Prints (1, 4), न्दीH🧑
 /// }
 /// ```
-pub fn num_graphemes(st: &str) -> usize {
-    UnicodeSegmentation::grapheme_indices(st, true).count()
+pub fn byte_range_to_grapheme_range(st: &str, start: usize, end: usize) -> (usize, usize) {
+    let first = st
+        .grapheme_indices(true)
+        .take_while(|(idx, grapheme)| idx + grapheme.len() <= start)
+        .count();
+    let last = st
+        .grapheme_indices(true)
+        .take_while(|(idx, _)| *idx < end)
+        .count();
+    (first, last.max(first))
 }
 
-/// Previoius Grapheme from current idx
+/// Where the Caret Lands After Typing inserted at idx
+///
+/// The insert goes at the grapheme boundary at or before idx, and the
+/// caret normally rests right after the inserted text.  If the insert
+/// merged with the text after it (ending in a ZWJ, or half a flag), that
+/// spot is inside a grapheme, so the caret moves on to the end of the
+/// merged grapheme.  A combining mark merging with the grapheme before
+/// the insert leaves the caret right after the mark.
+///
+/// The index is into the string after the insert.
 ///
 /// ```rust
 /// use grapheme_utils::*;
-/// 
+///
 /// fn main() {
-///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
-/// 
-///     println!("prev_grapheme_from_idx {}", prev_grapheme_from_idx(&st, 18)); // Prints न्दी
+///     println!("{}", caret_after_insert("eb", 1, "\u{301}")); // Prints 3, after é
+///     println!("{}", caret_after_insert("🇸", 0, "🇺")); // Prints 8, after the 🇺🇸 flag
 /// }
 /// ```
-pub fn prev_grapheme_from_idx(st: &str, idx: usize) -> &str {
-    if idx == 0 {
-        return "";
+pub fn caret_after_insert(st: &str, idx: usize, inserted: &str) -> usize {
+    let pos = grapheme_idx_at_idx(st, idx);
+    let combined = [&st[..pos], inserted, &st[pos..]].concat();
+    let target = pos + inserted.len();
+    if is_inside_grapheme(&combined, target) {
+        next_grapheme_idx_from_idx(&combined, target)
+    } else {
+        target
     }
-    let pos = prev_grapheme_idx_from_idx(&st, idx);
-    grapheme_at_idx(&st, pos)
 }
 
-/// Byte Index of the Previous Extended Grapheme from Current Idx
+/// Every Column a Caret Can Rest In
 ///
-/// NOTE: This will return 0, even when the string is empty.
-///
-/// Note: In testing, The Rust library currently divides
-///       some characters that should be singular "🧑🌾"
-///
-/// Note: This function has been modified to be panic proof.
-///       The underlying library can panic:
-///       unicode-segmentation-1.12.0/src/grapheme.rs:787:29:
+/// The starting column of each grapheme plus the end column.  A wide
+/// grapheme leaves a gap, there is no caret stop in its middle.  A zero
+/// width grapheme doesn't add a second stop at the same column.
 ///
 /// ```rust
 /// use grapheme_utils::*;
-/// 
+///
 /// fn main() {
 ///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
-/// 
-///     println!(
-///         "prev_grapheme_idx_from_idx {}",
-///         prev_grapheme_idx_from_idx(&st, 18)
-///     ); // Prints 6
+///
+///     println!("{:?}", caret_columns(&st));
+///     // Prints [0, 2, 5, 6, 8, 10, 11, 12, 13, 15, 16, 17, 18]
 /// }
 /// ```
-// Note: prev_boundary is written to only require
-//       a short chunk of the string instead of the
-//       whole thing:
-//           let beg = idx - prev-ch.len_utf8 - etc
-//           prev_boundary(st[beg..idx], beg)
-//
-//       This Code could be IMPROVED by implementing PrevChunk
-//
-//       next_boundary can then send an error back saying if
-//       it needs more (GraphemeIncomplete::PrevChunk)
-//
-//       beg MUST land on exact utf8 char
-//       boundaries, but it's really hard for prev_grapheme_idx
-//       where you need to know the exact info we're wanting.
-pub fn prev_grapheme_idx_from_idx(st: &str, idx: usize) -> usize {
-    let st_len = st.len();
-    if st_len == 0 {
-        return 0;
+pub fn caret_columns(st: &str) -> Vec<usize> {
+    let mut col = 0;
+    let mut columns = vec![0];
+    for grapheme in st.graphemes(true) {
+        let width = cluster_width(grapheme);
+        if width > 0 {
+            col += width;
+            columns.push(col);
+        }
     }
+    columns
+}
 
-    let max_len = st_len.saturating_sub(1);
-
-    let mut pos = idx;
-    while pos <= max_len && (st.as_bytes()[pos] & 0xc0) == 0x80 {
-        pos += 1;
-    }
-    if pos > st_len {
-        pos = st_len;
+// Byte ranges of the rows st wraps into at width, line breaks are forced
+// (a `\r` before the `\n` is left out of the row), each line is wrapped
+// like break_points_for_width
+fn wrapped_row_ranges(st: &str, width: usize) -> Vec<(usize, usize)> {
+    let mut rows = Vec::new();
+    let mut line_start = 0;
+    for line in st.split('\n') {
+        let next_line = line_start + line.len() + 1;
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let mut row_start = line_start;
+        for brk in break_points_for_width(line, width) {
+            rows.push((row_start, line_start + brk));
+            row_start = line_start + brk;
+        }
+        rows.push((row_start, line_start + line.len()));
+        line_start = next_line;
     }
+    rows
+}
 
-    let mut cursor = GraphemeCursor::new(pos, st_len, true);
-    let pos = match cursor.prev_boundary(st, 0) {
-        Ok(Some(prev)) => prev,
-        _ => 0, // If we can't find a valid breakpoint or are at the start, return 0
-    };
-    pos
+/// Wrapped Rows that Differ Between Two Versions of a String
+///
+/// Both strings are wrapped at width (grapheme wrap, `\n` always starts a
+/// new row) and the rows are compared in order.  Returns the row numbers
+/// that differ, rows only one version has included, so a TUI can redraw
+/// just those.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let old = "abcd\nefgh\nijkl";
+///     let new = "abcd\nefXgh\nijkl";
+///
+///     println!("{:?}", changed_rows(old, new, 4)); // Prints [1, 2, 3], ijkl moved down a row
+///     println!("{:?}", changed_rows(old, new, 8)); // Prints [1]
+/// }
+/// ```
+pub fn changed_rows(old: &str, new: &str, width: usize) -> Vec<usize> {
+    let old_rows: Vec<&str> = wrapped_row_ranges(old, width)
+        .into_iter()
+        .map(|(start, end)| &old[start..end])
+        .collect();
+    let new_rows: Vec<&str> = wrapped_row_ranges(new, width)
+        .into_iter()
+        .map(|(start, end)| &new[start..end])
+        .collect();
+    (0..old_rows.len().max(new_rows.len()))
+        .filter(|&row| old_rows.get(row) != new_rows.get(row))
+        .collect()
 }
 
-/// Return the string_width
+/// Clamp a Grapheme Number to the Graphemes in the String
+///
+/// Returns n, or num_graphemes(st) if n is past the end.
 ///
 /// ```rust
 /// use grapheme_utils::*;
-/// 
+///
 /// fn main() {
 ///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
-/// 
-///     println!("string_width {}", string_width(&st)); // Prints 18, the string uses 18 columns
+///
+///     println!("clamp_grapheme_number {}", clamp_grapheme_number(&st, 99)); // Prints 12
+///     println!("clamp_grapheme_number {}", clamp_grapheme_number(&st, 3)); // Prints 3
 /// }
 /// ```
-pub fn string_width(st: &str) -> usize {
-    let mut total = 0;
-    for (_, grapheme) in st.grapheme_indices(true) {
-        total += grapheme.width();
-    }
-    total
+pub fn clamp_grapheme_number(st: &str, n: usize) -> usize {
+    n.min(num_graphemes(st))
 }
 
+/// Signed Column Distance Between the Graphemes at Two Byte Indexes
+///
+/// Positive when to_idx is to the right of from_idx.  Like the rest of the
+/// crate, any index inside a grapheme counts as that grapheme's start, and
+/// an index past the end is the end of the string.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+///
+///     println!("col_distance {}", col_distance(&st, 0, 18)); // Prints 5
+///     println!("col_distance {}", col_distance(&st, 18, 0)); // Prints -5
+/// }
+/// ```
+pub fn col_distance(st: &str, from_idx: usize, to_idx: usize) -> isize {
+    idx_to_column(st, to_idx) as isize - idx_to_column(st, from_idx) as isize
+}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    type TestData = (
-        usize, // testnum
-        usize, // pgifi: prev_grapheme_idx_from_idx
-        usize, // giati: grapheme_idx_at_idx
-        usize, // nxgifi: next_grapheme_idx_from_idx
-        &'static str, // pgfi: prev_grapheme_from_idx
-        &'static str, // gati: grapheme_at_idx
-        &'static str, // nxgfi: next_grapheme_from_idx
-        usize, // gwi: grapheme width_idx
-        usize, // glen: grapheme_len
-        &'static str, // nthg: nth_grapheme
-        usize, // ngw: nth_grapheme width
-        usize, // nthgi: nth_grapheme_idx
-        usize, // numg: num_graphemes
-        usize, // sw: string_width
-    );
-
-    fn run_grapheme_test(st: &str, expected: Vec<TestData>) {
-        // Note:  Testing An Error:
-        // The Character 🧑🌾 is supposed to be 1 Character.
-        // (Look at it in a real editor)
-        // I expect this to be fixed eventually, but it's here for now.
-        //
-        // Note, the 2 éé are differnt.
-        // First one from a French AZERTY keyboard ( utf8 bytes c3a9, or codepoint e9)
-        // Second from the standard linux Ctrl-U character entry: (utf8 bytes 65 cc 81, or codepoint-modifier 65 301)
-        let string_len = st.len();
-        println!("Testing grapheme vector for \"{}\"", st);
-        assert_eq!(expected.len(), string_len + 2); // Ensure there's one more expected result than the length of the input
-
-        for i in 0..string_len + 2 {
-            print!("Testing String: \"{}\", at byte index: {} \n", st, i);
-            assert_eq!(expected[i].0, i); // Position index
-            print!("i:{} ok, \n", i);
-
-            let pgifi = prev_grapheme_idx_from_idx(st, i);
-            print!("prev_grapheme_idx_from_idx");
-            assert_eq!(expected[i].1, pgifi);
-            println!("  ok:{}", pgifi);
-
-            let giati = grapheme_idx_at_idx(st, i);
-            print!("grapheme_idx_at_idx");
-            assert_eq!(expected[i].2, giati);
-            println!("  ok:{}", giati);
+/// Collapse Long Runs of Zero Width Graphemes
+///
+/// Any run of more than max_run consecutive zero width graphemes (zero
+/// width spaces, stray joiners, BOMs...) is cut down to its first max_run.
+/// This defuses invisible padding floods while keeping a few legitimate
+/// joiners.  Everything else is copied unchanged.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = format!("a{}b", "\u{200B}".repeat(100));
+///
+///     println!("{}", collapse_zero_width_runs(&st, 2).len()); // Prints 8, a + 2 x 3 bytes + b
+/// }
+/// ```
+pub fn collapse_zero_width_runs(st: &str, max_run: usize) -> String {
+    let mut out = String::with_capacity(st.len());
+    let mut run = 0;
+    for grapheme in st.graphemes(true) {
+        if cluster_width(grapheme) == 0 {
+            run += 1;
+            if run > max_run {
+                continue;
+            }
+        } else {
+            run = 0;
+        }
+        out.push_str(grapheme);
+    }
+    out
+}
 
-            let nxgifi = next_grapheme_idx_from_idx(st, i);
-            print!("next_grapheme_idx_from_idx");
-            assert_eq!(expected[i].3, nxgifi);
-            println!("  ok:{}\n", nxgifi);
+/// Byte Index of the Grapheme Covering a Visual Column
+///
+/// The reverse of grapheme_width_at_idx, for mouse clicks: every column a
+/// wide grapheme covers gives the same byte start, just like any index
+/// inside a grapheme works for the index functions.  A column past the
+/// end returns st.len().
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+///
+///     println!("{}", column_to_idx(&st, 2)); // Prints 6, न्दी covers 2, 3 and 4
+///     println!("{}", column_to_idx(&st, 4)); // Prints 6
+///     println!("{}", column_to_idx(&st, 5)); // Prints 18, H
+/// }
+/// ```
+pub fn column_to_idx(st: &str, column: usize) -> usize {
+    let mut col = 0;
+    for (idx, _, width) in grapheme_spans(st) {
+        col += width;
+        if column < col {
+            return idx;
+        }
+    }
+    st.len()
+}
 
-            let pgfi = prev_grapheme_from_idx(st, i);
-            print!("prev_grapheme_from_idx");
-            assert_eq!(expected[i].4, pgfi);
-            println!("  ok:{}", pgfi);
+/// Shared Leading Graphemes of Two Strings, as a Slice of a
+///
+/// For autocomplete display.  Graphemes are compared whole, so "e" is not
+/// a common prefix of "e" and an accented e.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     println!("{}", common_prefix_str("abcdef", "abcXYZ")); // Prints abc
+/// }
+/// ```
+pub fn common_prefix_str<'a>(a: &'a str, b: &str) -> &'a str {
+    let len: usize = a
+        .graphemes(true)
+        .zip(b.graphemes(true))
+        .take_while(|(ga, gb)| ga == gb)
+        .map(|(ga, _)| ga.len())
+        .sum();
+    &a[..len]
+}
 
-            let gati = grapheme_at_idx(st, i);
-            print!("grapheme_at_idx");
-            assert_eq!(expected[i].5, gati);
-            println!("  ok:{}", gati);
+/// Number of Graphemes Needing a UTF-16 Surrogate Pair
+///
+/// Counts the graphemes with at least one scalar above U+FFFF, most emoji
+/// for example, for sizing UTF-16 buffers or flagging text headed for
+/// systems that only handle the Basic Multilingual Plane.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+///
+///     println!("{}", count_astral_graphemes(&st)); // Prints 2, 🧑 and 🌾
+/// }
+/// ```
+pub fn count_astral_graphemes(st: &str) -> usize {
+    st.graphemes(true)
+        .filter(|grapheme| grapheme.chars().any(|ch| ch > '\u{FFFF}'))
+        .count()
+}
 
-            let nxgfi = next_grapheme_from_idx(st, i);
-            print!("next_grapheme_from_idx");
-            assert_eq!(expected[i].6, nxgfi);
-            println!("  ok:{}\n", nxgfi);
+/// Scalars Making Up the Grapheme at idx, with Each Scalar's Own Width
+///
+/// For inspecting how a cluster is built: the base, combining marks,
+/// variation selectors, ZWJ and modifiers, in order.  The widths are per
+/// scalar, so they need not add up to the cluster's width.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+///
+///     println!("{:?}", decompose_grapheme(&st, 35)); // Prints [('‘', 1), ('\u{fe01}', 0)]
+/// }
+/// ```
+pub fn decompose_grapheme(st: &str, idx: usize) -> Vec<(char, usize)> {
+    grapheme_at_idx(st, idx)
+        .chars()
+        .map(|ch| (ch, cluster_width(ch.encode_utf8(&mut [0; 4]))))
+        .collect()
+}
 
-            let gwi = grapheme_width_at_idx(st, i);
-            print!("grapheme_width_from_idx");
-            assert_eq!(expected[i].7, gwi);
-            println!("  ok:{}", gwi);
+/// Pad the Narrower of Two Strings to the Wider One's Width
+///
+/// Trailing spaces are added by display width, like pad_to_width, so a
+/// pair of labels lines up.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     println!("{:?}", equalize_widths("hi", "你好")); // Prints ("hi  ", "你好")
+/// }
+/// ```
+pub fn equalize_widths(a: &str, b: &str) -> (String, String) {
+    let width = string_width(a).max(string_width(b));
+    (pad_to_width(a, width, ' '), pad_to_width(b, width, ' '))
+}
 
-            let glen = grapheme_len(st, i);
-            print!("grapheme_len");
-            assert_eq!(expected[i].8, glen);
-            println!("  ok:{}", glen);
+// Does needle match st at the grapheme start idx, ending on a grapheme
+// boundary too?
+fn is_aligned_match(st: &str, idx: usize, needle: &str) -> bool {
+    let end = idx + needle.len();
+    st.get(idx..end) == Some(needle) && !is_inside_grapheme(st, end)
+}
 
-            let nthg = nth_grapheme(st, i);
-            print!("nth_grapheme");
-            assert_eq!(expected[i].9, nthg);
-            println!("  ok:{}", nthg);
+/// Byte Index of Every Grapheme Aligned Match of needle
+///
+/// str::match_indices made grapheme safe, for highlighting all matches.
+/// A match must start and end on grapheme boundaries, so "e" doesn't
+/// match the start of an accented e.  Matches don't overlap, an empty
+/// needle matches nothing.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "e\u{301}xe\u{e9}e";
+///
+///     println!("{:?}", find_all_graphemes(st, "e")); // Prints [4, 7]
+/// }
+/// ```
+pub fn find_all_graphemes(st: &str, needle: &str) -> Vec<usize> {
+    let mut found = Vec::new();
+    if needle.is_empty() {
+        return found;
+    }
+    let mut next_free = 0;
+    for (idx, _) in st.grapheme_indices(true) {
+        if idx >= next_free && is_aligned_match(st, idx, needle) {
+            found.push(idx);
+            next_free = idx + needle.len();
+        }
+    }
+    found
+}
 
-            let ngw = nth_grapheme_width(st, i);
-            print!("nth grapheme_width");
-            assert_eq!(expected[i].10, ngw);
-            println!("  ok:{}", ngw);
+/// Grapheme Number Where Two Strings First Differ
+///
+/// Whole graphemes are compared, so "e" and "e\u{301}" differ at that
+/// grapheme.  If one string is a prefix of the other, the first grapheme
+/// past the shorter one is the difference.  Returns None if both strings
+/// are identical.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     println!("{:?}", first_grapheme_difference("abé", "abX")); // Prints Some(2)
+///     println!("{:?}", first_grapheme_difference("abé", "abé")); // Prints None
+/// }
+/// ```
+pub fn first_grapheme_difference(a: &str, b: &str) -> Option<usize> {
+    let mut a_graphemes = a.graphemes(true);
+    let mut b_graphemes = b.graphemes(true);
+    let mut n = 0;
+    loop {
+        match (a_graphemes.next(), b_graphemes.next()) {
+            (None, None) => return None,
+            (ga, gb) if ga != gb => return Some(n),
+            _ => n += 1,
+        }
+    }
+}
 
-            let nthgi = nth_grapheme_idx(st, i);
-            print!("nth_grapheme_idx");
-            assert_eq!(expected[i].11, nthgi);
-            println!("  ok:{}", nthgi);
+/// Width of the First Line
+///
+/// Returns the string_width of everything before the first `\n`, `\r`,
+/// or any other C0 control character (0x00 to 0x1f, tab included).
+///
+/// Controls always start a new grapheme, so this never splits one.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾\ne‘︀o‘︁réé".to_string();
+///
+///     println!("first_line_width {}", first_line_width(&st)); // Prints 10
+/// }
+/// ```
+pub fn first_line_width(st: &str) -> usize {
+    let end = st.bytes().position(|b| b < 0x20).unwrap_or(st.len());
+    string_width(&st[..end])
+}
 
-            let numg = num_graphemes(st);
-            print!("num_graphemes");
-            assert_eq!(expected[i].12, numg);
-            println!("  ok:{}", numg);
+/// Byte Index of the First Non-Printing Grapheme
+///
+/// A grapheme is non-printing if it starts with a control character (a
+/// bell, an escape, `\t`, `\n` ...) or is zero width without being a
+/// combining mark, like a stray zero width space or BOM.  Returns None if
+/// everything prints.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     println!("{:?}", first_nonprinting_idx("你好\x07!")); // Prints Some(6)
+///     println!("{:?}", first_nonprinting_idx("你好!")); // Prints None
+/// }
+/// ```
+pub fn first_nonprinting_idx(st: &str) -> Option<usize> {
+    st.grapheme_indices(true)
+        .find(|(_, grapheme)| {
+            let first = grapheme.chars().next().unwrap_or(' ');
+            first.is_control() || (cluster_width(grapheme) == 0 && !is_extending(first))
+        })
+        .map(|(idx, _)| idx)
+}
 
-            let sw = string_width(st);
-            print!("string_width");
-            assert_eq!(expected[i].13, sw);
-            println!("  ok:{}", sw);
-        }
+/// Grapheme Right After the First Match of needle
+///
+/// Only grapheme aligned matches count: "e" doesn't match the start of an
+/// accented e.  Returns None if there is no match, nothing follows the
+/// match, or needle is empty.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+///
+///     println!("{:?}", grapheme_after_match(&st, "H")); // Prints Some("🧑")
+///     println!("{:?}", grapheme_after_match(&st, "ré")); // Prints Some("e\u{301}")
+/// }
+/// ```
+pub fn grapheme_after_match<'a>(st: &'a str, needle: &str) -> Option<&'a str> {
+    if needle.is_empty() {
+        return None;
     }
+    st.grapheme_indices(true)
+        .find(|&(idx, _)| is_aligned_match(st, idx, needle))
+        .and_then(|(idx, _)| st[idx + needle.len()..].graphemes(true).next())
+}
 
-    #[test]
-    fn test_grapheme_vectors() {
-        let test_cases: Vec<(String, Vec<TestData>)> = vec![
-            (
-                "".to_string(),
-                vec![
-                    (0, 0, 0, 0, "", "", "", 0, 0, "", 0, 0, 0, 0),
-                    (1, 0, 0, 0, "", "", "", 0, 0, "", 0, 0, 0, 0),
+/// Byte Index of the Grapheme at a Fraction of the Way Through
+///
+/// fraction is clamped to 0.0..=1.0, and rounded to the nearest grapheme
+/// number, for "jump to the middle" or dragging a scrollbar.
+///
+/// Note: Like nth_grapheme_idx, 1.0 returns st.len(), the end of the string.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+///
+///     println!("grapheme_at_fraction {}", grapheme_at_fraction(&st, 0.5)); // Prints 28, grapheme 6
+/// }
+/// ```
+pub fn grapheme_at_fraction(st: &str, fraction: f64) -> usize {
+    let fraction = if fraction.is_nan() {
+        0.0
+    } else {
+        fraction.clamp(0.0, 1.0)
+    };
+    let n = (fraction * num_graphemes(st) as f64).round() as usize;
+    nth_grapheme_idx(st, n)
+}
+
+/// Return the grapheme at the given string idx
+///
+/// ```rust
+/// use grapheme_utils::*;
+/// 
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+/// 
+///     println!("grapheme_at_idx {}", grapheme_at_idx(&st, 18)); // Prints H
+/// }
+/// ```
+pub fn grapheme_at_idx(st: &str, idx: usize) -> &str {
+    let pos = grapheme_idx_at_idx(st, idx);
+    &st[pos..pos + st[pos..].graphemes(true).next().unwrap_or("").len()]
+}
+
+/// Grapheme Shown at a (row, col) of Wrapped Text, for Mouse Clicks
+///
+/// st is wrapped at term_width like changed_rows does (grapheme wrap,
+/// `\n` always starts a new row), then the grapheme covering col on row
+/// is returned.  Either column of a wide grapheme hits it.  A row or col
+/// past the text returns "".
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     println!("{}", grapheme_at_row_col("abcdef", 3, 1, 0)); // Prints d
+///     println!("{}", grapheme_at_row_col("a你好", 3, 1, 1)); // Prints 好
+/// }
+/// ```
+pub fn grapheme_at_row_col(st: &str, term_width: usize, row: usize, col: usize) -> &str {
+    let Some(&(start, end)) = wrapped_row_ranges(st, term_width).get(row) else {
+        return "";
+    };
+    let mut row_col = 0;
+    for grapheme in st[start..end].graphemes(true) {
+        row_col += cluster_width(grapheme);
+        if col < row_col {
+            return grapheme;
+        }
+    }
+    ""
+}
+
+/// UTF-8 Byte Length of Every Grapheme
+///
+/// The byte view to go with grapheme_widths, handy for length prefixed
+/// serialization.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+///
+///     println!("{:?}", grapheme_byte_lengths(&st));
+///     // Prints [6, 12, 1, 4, 4, 1, 6, 1, 6, 1, 2, 3]
+/// }
+/// ```
+pub fn grapheme_byte_lengths(st: &str) -> Vec<usize> {
+    st.graphemes(true).map(str::len).collect()
+}
+
+/// One Step of a grapheme_diff, Counts are in Graphemes
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DiffOp {
+    Equal(usize),   // Keep the next n graphemes of old
+    Insert(String), // Insert these graphemes
+    Delete(usize),  // Drop the next n graphemes of old
+}
+
+// Append op, merging it into the last op when they're the same kind
+fn push_diff_op(ops: &mut Vec<DiffOp>, op: DiffOp) {
+    match (ops.last_mut(), op) {
+        (Some(DiffOp::Equal(n)), DiffOp::Equal(m)) => *n += m,
+        (Some(DiffOp::Delete(n)), DiffOp::Delete(m)) => *n += m,
+        (Some(DiffOp::Insert(text)), DiffOp::Insert(more)) => text.push_str(&more),
+        (_, op) => ops.push(op),
+    }
+}
+
+/// Grapheme Diff from old to new as Equal / Insert / Delete Steps
+///
+/// Applying the ops in order to old gives new.  Graphemes are compared
+/// whole, so an accent or emoji is never half changed.  Where graphemes
+/// are replaced the Delete comes before the Insert.
+///
+/// Note: A simple longest common subsequence table, O(old * new), meant
+///       for lines and labels rather than whole files.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     println!("{:?}", grapheme_diff("abé", "abXé"));
+///     // Prints [Equal(2), Insert("X"), Equal(1)]
+/// }
+/// ```
+pub fn grapheme_diff(old: &str, new: &str) -> Vec<DiffOp> {
+    let a: Vec<&str> = old.graphemes(true).collect();
+    let b: Vec<&str> = new.graphemes(true).collect();
+
+    // lcs[i][j] is the common subsequence length of a[i..] and b[j..]
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut ops = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            push_diff_op(&mut ops, DiffOp::Equal(1));
+            i += 1;
+            j += 1;
+        } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            push_diff_op(&mut ops, DiffOp::Delete(1));
+            i += 1;
+        } else {
+            push_diff_op(&mut ops, DiffOp::Insert(b[j].to_string()));
+            j += 1;
+        }
+    }
+    ops
+}
+
+/// Everything Known About a Grapheme, see grapheme_full_iter
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GraphemeInfo<'a> {
+    pub byte_start: usize, // String byte index of the grapheme
+    pub col_start: usize,  // Visual column the grapheme starts in
+    pub width: usize,      // Visual column width
+    pub text: &'a str,     // The extended grapheme cluster itself
+    pub number: usize,     // nth grapheme, base 0
+}
+
+/// Iterate Graphemes with their Byte Index, Column, Width and Number
+///
+/// One pass gives renderers, hit-testers and diff tools everything
+/// without recomputing anything.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+///
+///     let info = grapheme_full_iter(&st).nth(2).unwrap();
+///     println!("{} {} {} {} {}", info.byte_start, info.col_start, info.width, info.text, info.number);
+///     // Prints 18 5 1 H 2
+/// }
+/// ```
+pub fn grapheme_full_iter(st: &str) -> impl Iterator<Item = GraphemeInfo<'_>> {
+    st.grapheme_indices(true)
+        .enumerate()
+        .scan(0, |col, (number, (byte_start, text))| {
+            let width = cluster_width(text);
+            let info = GraphemeInfo {
+                byte_start,
+                col_start: *col,
+                width,
+                text,
+                number,
+            };
+            *col += width;
+            Some(info)
+        })
+}
+
+/// Grapheme length in Bytes
+///
+/// ```rust
+/// use grapheme_utils::*;
+/// 
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+/// 
+///     let num = 7; // Anything between 6 and 17 inclusive
+/// 
+///     // Grapheme utf8 byte count or length in bytes
+///     println!("grapheme_len {}", grapheme_len(&st, num)); // Prints 12 (न्दी uses 12 utf8 bytes)
+///     println!("grapheme_len {}", grapheme_len(&st, 18)); // Prints 1 (H uses 1 utf8 byte)
+/// }
+/// ```
+pub fn grapheme_len(st: &str, idx: usize) -> usize {
+    let pos = grapheme_idx_at_idx(st, idx);
+    st[pos..].graphemes(true).next().unwrap_or("").len()
+}
+
+/// Starting idx of Grapheme
+///
+/// This returns the starting index for the grapheme given
+/// a string byte idx
+///
+/// Note: This is an example wasteful function, but it
+///       usually returns the same index you're providing.
+///       
+///   
+/// ```rust
+/// use grapheme_utils::*;
+/// 
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+/// 
+///     println!("grapheme_idx_at_idx {}", grapheme_idx_at_idx(&st, 18)); // Prints 18
+/// }
+/// ```
+pub fn grapheme_idx_at_idx(st: &str, idx: usize) -> usize {
+    if idx == 0 {
+        return 0;
+    }
+    let mut pos = idx;
+
+    if idx >= st.len() {
+        return st.len();
+    }
+    let mut cursor = GraphemeCursor::new(idx, st.len(), true);
+
+    loop {
+        while pos > 0 && (st.as_bytes()[pos] & 0xc0) == 0x80 {
+            pos -= 1;
+        }
+        cursor.set_cursor(pos);
+        if cursor.is_boundary(st, 0).unwrap_or(false) {
+            break;
+        }
+        pos -= 1;
+    }
+    pos
+}
+
+/// HashMap Key with Grapheme Cluster Sequence Identity
+///
+/// Two keys are equal when they hold the same sequence of clusters, and
+/// the hash is built cluster by cluster, so the boundaries are part of it.
+/// Today that matches plain &str identity, the wrapper documents the intent
+/// and gives normalization a single place to plug in.
+///
+/// ```rust
+/// use grapheme_utils::*;
+/// use std::collections::HashMap;
+///
+/// fn main() {
+///     let mut seen = HashMap::new();
+///     seen.insert(GraphemeKey("न्दी"), 1);
+///
+///     println!("{:?}", seen.get(&GraphemeKey("न्दी"))); // Prints Some(1)
+/// }
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct GraphemeKey<'a>(pub &'a str);
+
+impl Hash for GraphemeKey<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for grapheme in self.0.graphemes(true) {
+            grapheme.hash(state);
+        }
+    }
+}
+
+impl PartialEq for GraphemeKey<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.graphemes(true).eq(other.0.graphemes(true))
+    }
+}
+
+impl Eq for GraphemeKey<'_> {}
+
+/// Starting Offset of Every Grapheme, Relative to a Parent Buffer
+///
+/// When st is a sub-slice starting at byte base of a larger buffer (a
+/// rope chunk, a piece table piece), this returns each grapheme start as
+/// an offset into the parent.  Use a base of 0 for plain offsets.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+///
+///     println!("{:?}", grapheme_offsets_with_base(&st, 100));
+///     // Prints [100, 106, 118, 119, 123, 127, 128, 134, 135, 141, 142, 144]
+/// }
+/// ```
+pub fn grapheme_offsets_with_base(st: &str, base: usize) -> Vec<usize> {
+    st.grapheme_indices(true)
+        .map(|(idx, _)| base + idx)
+        .collect()
+}
+
+/// How a Grapheme Wants to be Drawn, see grapheme_presentation
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Presentation {
+    Emoji,   // Color emoji font
+    Text,    // Emoji capable, but drawn as plain (usually 1 column) text
+    Neutral, // Not an emoji at all
+}
+
+// Is ch an emoji capable scalar?
+//
+// A hand made approximation of Extended_Pictographic, the blocks emoji
+// live in plus the scattered older symbols that have emoji forms.
+fn is_emoji_scalar(ch: char) -> bool {
+    matches!(ch as u32,
+        0x00A9 | 0x00AE | 0x203C | 0x2049 | 0x2122 | 0x2139
+        | 0x2194..=0x2199 | 0x21A9..=0x21AA | 0x231A..=0x231B | 0x2328 | 0x23CF
+        | 0x23E9..=0x23F3 | 0x23F8..=0x23FA | 0x24C2 | 0x25AA..=0x25AB | 0x25B6
+        | 0x25C0 | 0x25FB..=0x25FE | 0x2600..=0x27BF | 0x2934..=0x2935
+        | 0x2B05..=0x2B07 | 0x2B1B..=0x2B1C | 0x2B50 | 0x2B55 | 0x3030 | 0x303D
+        | 0x3297 | 0x3299 | 0x1F000..=0x1FAFF)
+}
+
+/// Emoji or Text Presentation of the Grapheme at idx
+///
+/// - A trailing U+FE0F (VS16) asks for Emoji, a U+FE0E (VS15) for Text
+/// - Otherwise an emoji capable grapheme uses its default presentation,
+///   Emoji when it's 2 columns wide (😊), Text when it's 1 (☺)
+/// - Anything else is Neutral
+///
+/// Note: Only VS15 and VS16 choose a presentation.  The other variation
+///       selectors, like the U+FE00 and U+FE01 after the quotes in ‘︀o‘︁,
+///       pick glyph variants and don't make a grapheme emoji.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "a😊☺\u{FE0F}".to_string();
+///
+///     println!("{:?}", grapheme_presentation(&st, 0)); // Prints Neutral
+///     println!("{:?}", grapheme_presentation(&st, 1)); // Prints Emoji
+///     println!("{:?}", grapheme_presentation(&st, 5)); // Prints Emoji
+///     println!("{:?}", grapheme_presentation("☺", 0)); // Prints Text
+///     println!("{:?}", grapheme_presentation("😊\u{FE0E}", 0)); // Prints Text
+/// }
+/// ```
+pub fn grapheme_presentation(st: &str, idx: usize) -> Presentation {
+    let grapheme = grapheme_at_idx(st, idx);
+    match grapheme
+        .chars()
+        .rev()
+        .find(|ch| matches!(ch, '\u{FE0E}' | '\u{FE0F}'))
+    {
+        Some('\u{FE0F}') => return Presentation::Emoji,
+        Some(_) => return Presentation::Text,
+        None => {}
+    }
+    match grapheme.chars().next() {
+        Some(ch) if is_emoji_scalar(ch) => {
+            if cluster_width(grapheme) >= 2 {
+                Presentation::Emoji
+            } else {
+                Presentation::Text
+            }
+        }
+        _ => Presentation::Neutral,
+    }
+}
+
+// Levenshtein edit distance counting whole graphemes, one row at a time
+fn grapheme_levenshtein(a: &[&str], b: &[&str]) -> usize {
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ga) in a.iter().enumerate() {
+        let mut diag = row[0];
+        row[0] = i + 1;
+        for (j, gb) in b.iter().enumerate() {
+            let cost = if ga == gb { diag } else { diag + 1 };
+            diag = row[j + 1];
+            row[j + 1] = cost.min(row[j] + 1).min(diag + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Similarity Ratio of Two Strings, Grapheme by Grapheme
+///
+/// 1.0 - edit_distance / longer_length, where both the Levenshtein edit
+/// distance and the lengths count graphemes.  1.0 is identical, 0.0 is
+/// nothing in common.  Two empty strings are identical.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     println!("{}", grapheme_similarity("kitten", "sitting")); // Prints 0.5714285714285714
+///     println!("{}", grapheme_similarity("🧑🌾", "🧑🌾")); // Prints 1
+/// }
+/// ```
+pub fn grapheme_similarity(a: &str, b: &str) -> f64 {
+    let a: Vec<&str> = a.graphemes(true).collect();
+    let b: Vec<&str> = b.graphemes(true).collect();
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+    1.0 - grapheme_levenshtein(&a, &b) as f64 / longest as f64
+}
+
+/// Sort Key Comparing Strings Grapheme by Grapheme
+///
+/// Byte order can put a combining sequence between unrelated strings, an
+/// e with an accent sorts after "ez" but before "e中".  Comparing the
+/// cluster vectors instead orders by the first differing grapheme, so a
+/// bare e always sorts before an accented one.
+///
+/// Note: No normalization is done, precomposed and decomposed forms are
+///       still different keys.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let mut words = vec!["e\u{301}", "e中", "ez"];
+///     words.sort_by_key(|w| grapheme_sort_key(w));
+///
+///     println!("{:?}", words); // Prints ["ez", "e中", "é"]
+/// }
+/// ```
+pub fn grapheme_sort_key(st: &str) -> Vec<String> {
+    st.graphemes(true).map(String::from).collect()
+}
+
+/// Iterate Graphemes with their Byte Index and Width
+///
+/// Yields (byte_idx, grapheme, width) in one pass, the same walk
+/// string_width sums up.  See grapheme_full_iter for columns and numbers
+/// too.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+///
+///     println!("{:?}", grapheme_spans(&st).nth(4)); // Prints Some((23, "🌾", 2))
+/// }
+/// ```
+pub fn grapheme_spans(st: &str) -> impl Iterator<Item = (usize, &str, usize)> {
+    st.grapheme_indices(true)
+        .map(|(idx, grapheme)| (idx, grapheme, cluster_width(grapheme)))
+}
+
+/// Return the grapheme starting at or after the given byte index in a string.
+///
+/// ```rust
+/// use grapheme_utils::*;
+/// 
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+/// 
+///     // Grapheme Visual Column Width
+///     println!("grapheme_width_at_idx {}", grapheme_width_at_idx(&st, 18)); // Prints 1 (H is 1 column wide)
+///
+///     let num = 7; // Anything between 6 and 17 inclusive
+///     println!(
+///         "grapheme_width_at_idx {}",
+///         grapheme_width_at_idx(&st, num)
+///     ); // Prints 3 (न्दी is 3 columns wide)
+/// }
+/// ```
+pub fn grapheme_width_at_idx(st: &str, idx: usize) -> usize {
+    let pos = grapheme_idx_at_idx(st, idx);
+    cluster_width(st[pos..].graphemes(true).next().unwrap_or(""))
+}
+
+/// Visual Column Width of Every Grapheme
+///
+/// Widths are 0, 1 or 2 in practice, so they are stored as u8 (clamped
+/// to 255) to keep the cache small for long lines.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+///
+///     println!("{:?}", grapheme_widths(&st)); // Prints [2, 3, 1, 2, 2, 1, 1, 1, 2, 1, 1, 1]
+/// }
+/// ```
+pub fn grapheme_widths(st: &str) -> Vec<u8> {
+    st.graphemes(true)
+        .map(|grapheme| cluster_width(grapheme).min(u8::MAX as usize) as u8)
+        .collect()
+}
+
+/// Iterate Graphemes, Flagging the Ones that Start a Word
+///
+/// Yields (grapheme, starts_word).  Words follow the unicode_segmentation
+/// word bounds, and only segments with a letter or digit count as words
+/// (the same words as unicode_words), so spaces and punctuation are never
+/// flagged.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "hello world".to_string();
+///
+///     for (grapheme, starts_word) in grapheme_word_flags(&st) {
+///         if starts_word {
+///             println!("{}", grapheme);
+///         }
+///     }
+///     // Prints
+///     // h
+///     // w
+/// }
+/// ```
+pub fn grapheme_word_flags(st: &str) -> impl Iterator<Item = (&str, bool)> {
+    let mut word_starts = st
+        .split_word_bound_indices()
+        .filter(|(_, word)| word.chars().any(char::is_alphanumeric))
+        .map(|(idx, _)| idx)
+        .peekable();
+    st.grapheme_indices(true).map(move |(idx, grapheme)| {
+        while word_starts.next_if(|&start| start < idx).is_some() {}
+        (grapheme, word_starts.next_if_eq(&idx).is_some())
+    })
+}
+
+/// Precomputed Grapheme Offsets for O(1) nth Lookups
+///
+/// nth_grapheme and nth_grapheme_idx walk the string from the start on
+/// every call.  Build a GraphemeMap once (O(n)) and nth(), nth_idx() and
+/// len() are O(1) from then on.  Forgiving like the free functions: past
+/// the end, nth() is "" and nth_idx() is st.len().
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+///     let map = GraphemeMap::new(&st);
+///
+///     println!("{} {} {}", map.len(), map.nth(1), map.nth_idx(2)); // Prints 12 न्दी 18
+///     println!("[{}] {}", map.nth(99), map.nth_idx(99)); // Prints [] 47
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct GraphemeMap<'a> {
+    slice: GraphemeSlice<'a>,
+}
+
+impl<'a> GraphemeMap<'a> {
+    /// Precompute the grapheme offsets of st
+    pub fn new(st: &'a str) -> Self {
+        GraphemeMap {
+            slice: GraphemeSlice::new(st),
+        }
+    }
+
+    /// Number of graphemes
+    pub fn len(&self) -> usize {
+        self.slice.len()
+    }
+
+    /// True when there are no graphemes
+    pub fn is_empty(&self) -> bool {
+        self.slice.is_empty()
+    }
+
+    /// nth grapheme, or "" if n is past the end
+    pub fn nth(&self, n: usize) -> &'a str {
+        self.slice.get(n).unwrap_or("")
+    }
+
+    /// Byte index of the nth grapheme, or st.len() if n is past the end
+    pub fn nth_idx(&self, n: usize) -> usize {
+        self.slice.offsets[n.min(self.len())]
+    }
+}
+
+/// Iterate Graphemes from the End, with their Byte Index
+///
+/// Yields (byte_idx, grapheme) from the last grapheme to the first, for
+/// scanning backwards, like trimming trailing whitespace grapheme by
+/// grapheme.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+///
+///     println!("{:?}", graphemes_rev(&st).nth(1)); // Prints Some((42, "é"))
+/// }
+/// ```
+pub fn graphemes_rev(st: &str) -> impl Iterator<Item = (usize, &str)> {
+    st.grapheme_indices(true).rev()
+}
+
+/// Graphemes, with Every NUL Replaced by repl
+///
+/// For logs and binary-ish text with embedded NUL bytes, which terminals
+/// draw badly or not at all.  A NUL is a control character, always a
+/// grapheme of its own, so each one becomes a repl entry (repl is used
+/// as is, even if it's more than one grapheme, like "\\0").
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     println!("{:?}", graphemes_treating_nul_as("a\0é", "␀")); // Prints ["a", "␀", "é"]
+/// }
+/// ```
+pub fn graphemes_treating_nul_as(st: &str, repl: &str) -> Vec<String> {
+    st.graphemes(true)
+        .map(|grapheme| if grapheme == "\0" { repl } else { grapheme }.to_string())
+        .collect()
+}
+
+/// Graphemes, with a Custom Rule that Can Glue Neighbors Together
+///
+/// no_break(left, right) is asked at every grapheme boundary, returning
+/// true removes the boundary so right becomes part of left.  left is
+/// everything glued so far, so a rule can keep a whole custom tag sequence
+/// together.  Returns slices of st.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+///
+///     // Glue the two halves of the farmer back together
+///     let graphemes = graphemes_with_override(&st, |left, right| {
+///         left == "🧑" && right == "🌾"
+///     });
+///     println!("{} {}", graphemes.len(), graphemes[3]); // Prints 11 🧑🌾
+/// }
+/// ```
+pub fn graphemes_with_override(st: &str, no_break: impl Fn(&str, &str) -> bool) -> Vec<&str> {
+    let mut out: Vec<&str> = Vec::new();
+    let mut start = 0;
+    for (idx, grapheme) in st.grapheme_indices(true) {
+        if idx > 0 && !no_break(&st[start..idx], grapheme) {
+            out.push(&st[start..idx]);
+            start = idx;
+        }
+    }
+    if start < st.len() {
+        out.push(&st[start..]);
+    }
+    out
+}
+
+/// Array-like View of the Graphemes in a &str
+///
+/// The grapheme offsets are computed once in new(), after that
+/// slice[n], get(n) and len() don't have to walk the string again.
+///
+/// Note: Like a Vec, slice[n] panics when n is out of range.
+///       Use get(n) for the forgiving version.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+///     let slice = GraphemeSlice::new(&st);
+///
+///     println!("len {}", slice.len()); // Prints 12
+///     println!("slice[1] {}", &slice[1]); // Prints न्दी
+///     println!("get(12) {:?}", slice.get(12)); // Prints None
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct GraphemeSlice<'a> {
+    st: &'a str,
+    offsets: Vec<usize>, // Start of each grapheme, plus st.len()
+}
+
+impl<'a> GraphemeSlice<'a> {
+    /// Precompute the grapheme offsets of st
+    pub fn new(st: &'a str) -> Self {
+        let mut offsets: Vec<usize> = st.grapheme_indices(true).map(|(idx, _)| idx).collect();
+        offsets.push(st.len());
+        GraphemeSlice { st, offsets }
+    }
+
+    /// Number of graphemes
+    pub fn len(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    /// True when there are no graphemes
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// nth grapheme, or None if n is out of range
+    pub fn get(&self, n: usize) -> Option<&'a str> {
+        if n >= self.len() {
+            return None;
+        }
+        Some(&self.st[self.offsets[n]..self.offsets[n + 1]])
+    }
+
+    /// Iterate over the graphemes in order
+    pub fn iter(&self) -> impl Iterator<Item = &'a str> + '_ {
+        self.offsets.windows(2).map(|w| &self.st[w[0]..w[1]])
+    }
+}
+
+impl Index<usize> for GraphemeSlice<'_> {
+    type Output = str;
+
+    fn index(&self, n: usize) -> &str {
+        match self.get(n) {
+            Some(grapheme) => grapheme,
+            None => panic!(
+                "grapheme index out of range: the len is {} but the index is {}",
+                self.len(),
+                n
+            ),
+        }
+    }
+}
+
+/// Lay Items Out in an ls Style Grid
+///
+/// Items fill the grid row by row, cols per row.  Every cell is padded to
+/// the display width of the widest item, with gap spaces between columns.
+/// The last cell of a row isn't padded and rows are separated by `\n`, no
+/// trailing newline.  A cols of 0 is treated as 1.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let items = ["a", "你好", "🧑b", "xyz", "é"];
+///
+///     println!("{}", grid_layout(&items, 2, 1));
+///     // Prints
+///     // a    你好
+///     // 🧑b  xyz
+///     // é
+/// }
+/// ```
+pub fn grid_layout(items: &[&str], cols: usize, gap: usize) -> String {
+    let cell_width = items
+        .iter()
+        .map(|item| string_width(item))
+        .max()
+        .unwrap_or(0);
+    let mut out = String::new();
+    for (row_number, row) in items.chunks(cols.max(1)).enumerate() {
+        if row_number > 0 {
+            out.push('\n');
+        }
+        for (col, item) in row.iter().enumerate() {
+            if col > 0 {
+                push_fill(&mut out, gap, ' ');
+            }
+            out.push_str(item);
+            if col + 1 < row.len() {
+                push_fill(&mut out, cell_width - string_width(item), ' ');
+            }
+        }
+    }
+    out
+}
+
+// Is the grapheme East Asian Ambiguous width, 1 column in most terminals
+// but 2 in CJK locales?  A registered width override pins the width, so
+// it is not ambiguous.
+fn is_ambiguous_width(grapheme: &str) -> bool {
+    width_override(grapheme).is_none() && grapheme.width() != grapheme.width_cjk()
+}
+
+/// Does the String Contain East Asian Ambiguous Width Graphemes
+///
+/// Ambiguous graphemes (like ‘ or ±) are 1 column in most terminals but 2
+/// in CJK locales, so aligned output containing them can break depending
+/// on the user's terminal settings.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     println!("{}", has_ambiguous_width("“quoted”")); // Prints true
+///     println!("{}", has_ambiguous_width("\"quoted\"")); // Prints false
+/// }
+/// ```
+pub fn has_ambiguous_width(st: &str) -> bool {
+    st.graphemes(true).any(is_ambiguous_width)
+}
+
+/// Does the String Contain Any Wide (2+ Column) Graphemes
+///
+/// Stops at the first wide grapheme, so it's cheap for picking a fast
+/// ascii-like rendering path.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+///
+///     println!("has_wide_graphemes {}", has_wide_graphemes(&st)); // Prints true
+///     println!("has_wide_graphemes {}", has_wide_graphemes("hello")); // Prints false
+/// }
+/// ```
+pub fn has_wide_graphemes(st: &str) -> bool {
+    st.graphemes(true)
+        .any(|grapheme| cluster_width(grapheme) >= 2)
+}
+
+/// Join Two Blocks of Text Side by Side
+///
+/// Each line of left is padded with spaces to the width of the widest
+/// left line (display width, so CJK and emoji line up), then gap spaces,
+/// then the matching line of right.  The shorter block is padded with
+/// blank lines.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let left = "你好\nab".to_string();
+///     let right = "x\ny\nz".to_string();
+///
+///     println!("{}", hconcat(&left, &right, 1));
+///     // Prints
+///     // 你好 x
+///     // ab   y
+///     //      z
+/// }
+/// ```
+pub fn hconcat(left: &str, right: &str, gap: usize) -> String {
+    let left_lines: Vec<&str> = left.lines().collect();
+    let right_lines: Vec<&str> = right.lines().collect();
+    let left_width = required_width(left);
+
+    let mut out = String::new();
+    for row in 0..left_lines.len().max(right_lines.len()) {
+        if row > 0 {
+            out.push('\n');
+        }
+        let line = left_lines.get(row).copied().unwrap_or("");
+        out.push_str(line);
+        let fill = left_width - string_width(line) + gap;
+        out.extend(std::iter::repeat_n(' ', fill));
+        out.push_str(right_lines.get(row).copied().unwrap_or(""));
+    }
+    out
+}
+
+/// Byte Index of a (line, col) Position, for "Go to line:col"
+///
+/// The reverse of located_graphemes: line is base 0 and col is a display
+/// column.  Returns the start of the grapheme covering col, so the middle
+/// of a wide grapheme snaps to its start.  A col past the end of the line
+/// gives the end of that line (its `\n`), a line past the end gives
+/// st.len().
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "a你\nb好c";
+///
+///     println!("{}", idx_of_line_col(st, 1, 0)); // Prints 5, b
+///     println!("{}", idx_of_line_col(st, 1, 2)); // Prints 6, 好
+///     println!("{}", idx_of_line_col(st, 0, 9)); // Prints 4, the \n
+/// }
+/// ```
+pub fn idx_of_line_col(st: &str, line: usize, col: usize) -> usize {
+    st.grapheme_indices(true)
+        .zip(located_graphemes(st))
+        .find(|&(_, (at_line, at_col, grapheme))| {
+            at_line > line
+                || (at_line == line
+                    && (col < at_col + cluster_width(grapheme)
+                        || grapheme == "\n"
+                        || grapheme == "\r\n"))
+        })
+        .map_or(st.len(), |((idx, _), _)| idx)
+}
+
+/// Visual Column of the Grapheme at a Byte Index
+///
+/// The widths of all the graphemes before the one containing idx, so the
+/// column the cursor is drawn in.  An idx inside a grapheme uses its start
+/// and an idx past the end gives the whole string_width.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+///
+///     println!("{}", idx_to_column(&st, 18)); // Prints 5, after हि and न्दी
+///     println!("{}", idx_to_column(&st, 10)); // Prints 2, inside न्दी
+/// }
+/// ```
+pub fn idx_to_column(st: &str, idx: usize) -> usize {
+    string_width(&st[..grapheme_idx_at_idx(st, idx)])
+}
+
+/// Rendered Width of a Line Starting at Column indent, Tabs Included
+///
+/// indent + string_width(st), except a tab advances to the next tab stop
+/// (every 8 columns) counted from column 0 of the screen, so a leading tab
+/// expands from indent and not from 0.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     println!("{}", indented_width("\tab", 4)); // Prints 10, the tab covers 4..8
+///     println!("{}", indented_width("你好", 4)); // Prints 8
+/// }
+/// ```
+pub fn indented_width(st: &str, indent: usize) -> usize {
+    let mut col = indent;
+    for grapheme in st.graphemes(true) {
+        if grapheme == "\t" {
+            col = (col / 8 + 1) * 8;
+        } else {
+            col += cluster_width(grapheme);
+        }
+    }
+    col
+}
+
+// One grapheme of is_monospace_safe
+fn is_monospace_safe_grapheme(grapheme: &str) -> bool {
+    cluster_width(grapheme) > 0
+        && !is_ambiguous_width(grapheme)
+        && !grapheme.chars().any(char::is_control)
+}
+
+/// Does Inserting Text Keep the String Monospace Safe?
+///
+/// For a table editor rejecting bad pastes.  inserted goes at the grapheme
+/// boundary at or before idx, then every grapheme the insert touches,
+/// including one it merged with, is checked like is_monospace_safe does.
+/// Problems elsewhere in st, that were there before, don't count.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     println!("{}", insert_keeps_monospace("cell", 2, "xy")); // Prints true
+///     println!("{}", insert_keeps_monospace("cell", 2, "\t")); // Prints false
+/// }
+/// ```
+pub fn insert_keeps_monospace(st: &str, idx: usize, inserted: &str) -> bool {
+    let pos = grapheme_idx_at_idx(st, idx);
+    let combined = [&st[..pos], inserted, &st[pos..]].concat();
+    let end = pos + inserted.len();
+    combined
+        .grapheme_indices(true)
+        .filter(|(start, grapheme)| *start < end && start + grapheme.len() > pos)
+        .all(|(_, grapheme)| is_monospace_safe_grapheme(grapheme))
+}
+
+/// Would inserted Merge with the Last Grapheme of before?
+///
+/// True when the first grapheme of inserted joins the last grapheme of
+/// before, like a combining mark typed after a base letter, so an insert
+/// changed an existing grapheme (which matters for undo granularity).
+/// The two strings are never concatenated, a GraphemeCursor is run over
+/// them as two chunks.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     println!("{}", insertion_merges("e", "\u{301}")); // Prints true
+///     println!("{}", insertion_merges("e", "x")); // Prints false
+/// }
+/// ```
+pub fn insertion_merges(before: &str, inserted: &str) -> bool {
+    if before.is_empty() || inserted.is_empty() {
+        return false;
+    }
+    let join = before.len();
+    let mut cursor = GraphemeCursor::new(join, join + inserted.len(), true);
+    loop {
+        match cursor.is_boundary(inserted, join) {
+            Ok(is_boundary) => return !is_boundary,
+            Err(GraphemeIncomplete::PreContext(end)) => {
+                cursor.provide_context(&before[..end], 0);
+            }
+            Err(_) => return false,
+        }
+    }
+}
+
+/// Alternate the Graphemes of Two Strings
+///
+/// a[0], b[0], a[1], b[1] ... and then whatever is left of the longer one,
+/// for character by character transition effects that keep emoji and
+/// accents whole.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     println!("{}", interleave_graphemes("abc", "😊😊")); // Prints a😊b😊c
+/// }
+/// ```
+pub fn interleave_graphemes(a: &str, b: &str) -> String {
+    let mut out = String::with_capacity(a.len() + b.len());
+    let mut a = a.graphemes(true);
+    let mut b = b.graphemes(true);
+    loop {
+        match (a.next(), b.next()) {
+            (None, None) => return out,
+            (ga, gb) => {
+                out.push_str(ga.unwrap_or(""));
+                out.push_str(gb.unwrap_or(""));
+            }
+        }
+    }
+}
+
+/// Is idx Strictly Inside a Grapheme
+///
+/// True when idx falls between the start and end of a grapheme, in other
+/// words when slicing the string at idx would corrupt a grapheme.
+/// Grapheme starts, st.len() and anything past the end are all false.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+///
+///     println!("is_inside_grapheme {}", is_inside_grapheme(&st, 7)); // Prints true, inside न्दी
+///     println!("is_inside_grapheme {}", is_inside_grapheme(&st, 6)); // Prints false, न्दी starts at 6
+/// }
+/// ```
+pub fn is_inside_grapheme(st: &str, idx: usize) -> bool {
+    idx < st.len() && grapheme_idx_at_idx(st, idx) != idx
+}
+
+/// Is the Grapheme at idx a Broken Emoji Fragment
+///
+/// Returns true for clusters left behind by truncated copy-paste, which
+/// usually render as tofu:
+///   - a dangling Zero Width Joiner (U+200D) at the start or end
+///   - a lone variation selector (U+FE00..U+FE0F, U+E0100..U+E01EF)
+///   - an isolated skin-tone modifier (U+1F3FB..U+1F3FF)
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "👍\u{200D}".to_string();
+///
+///     println!("is_malformed_emoji {}", is_malformed_emoji(&st, 0)); // Prints true
+///     println!("is_malformed_emoji {}", is_malformed_emoji("👍🏻", 0)); // Prints false
+/// }
+/// ```
+pub fn is_malformed_emoji(st: &str, idx: usize) -> bool {
+    let grapheme = grapheme_at_idx(st, idx);
+    let first = match grapheme.chars().next() {
+        Some(ch) => ch,
+        None => return false,
+    };
+    matches!(
+        first,
+        '\u{200D}' | '\u{FE00}'..='\u{FE0F}' | '\u{E0100}'..='\u{E01EF}' | '\u{1F3FB}'..='\u{1F3FF}'
+    ) || grapheme.ends_with('\u{200D}')
+}
+
+/// Will the String Stay Aligned in a Monospace Grid
+///
+/// Returns false if any grapheme:
+///   - is zero width (a lone combining mark, zero width space, etc)
+///   - is East Asian Ambiguous width (width differs by terminal locale)
+///   - contains a tab
+///   - contains any other control character (C0, DEL or C1)
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     println!("is_monospace_safe {}", is_monospace_safe("abc")); // Prints true
+///     println!("is_monospace_safe {}", is_monospace_safe("a\tb")); // Prints false
+/// }
+/// ```
+pub fn is_monospace_safe(st: &str) -> bool {
+    st.graphemes(true).all(is_monospace_safe_grapheme)
+}
+
+/// Is the String Exactly One Grapheme
+///
+/// For validating single character input, like a key binding glyph.  Same
+/// as num_graphemes(st) == 1, but stops at the second grapheme instead of
+/// counting a long accidental paste.
+///
+/// Note: The farmer in the README string, 🧑🌾 without a zero width joiner,
+///       is two graphemes, so it isn't a single grapheme.  With the joiner
+///       it is.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     println!("{}", is_single_grapheme("🧑")); // Prints true
+///     println!("{}", is_single_grapheme("न्दी")); // Prints true
+///     println!("{}", is_single_grapheme("ab")); // Prints false
+/// }
+/// ```
+pub fn is_single_grapheme(st: &str) -> bool {
+    let mut graphemes = st.graphemes(true);
+    graphemes.next().is_some() && graphemes.next().is_none()
+}
+
+/// Byte Index Just Past the Last Grapheme that Fully Fits in max_width
+///
+/// Everything from the returned index on is the overflowing tail, for
+/// fading out a partially visible final grapheme.  A wide grapheme that
+/// straddles max_width doesn't fit, so the index stops before it even
+/// though its first column is still visible; only then does this differ from
+/// cutting at the column.  Zero width graphemes right at the edge fit.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+///
+///     println!("{}", last_full_cluster_end(&st, 6)); // Prints 19, through H
+///     println!("{}", last_full_cluster_end(&st, 7)); // Prints 19, 🧑 straddles 6..8
+///     println!("{}", last_full_cluster_end(&st, 8)); // Prints 23
+/// }
+/// ```
+pub fn last_full_cluster_end(st: &str, max_width: usize) -> usize {
+    let mut col = 0;
+    for (idx, grapheme) in st.grapheme_indices(true) {
+        col += cluster_width(grapheme);
+        if col > max_width {
+            return idx;
+        }
+    }
+    st.len()
+}
+
+/// Display Width of Every Line in a Buffer, Kept Up to Date Across Edits
+///
+/// Editing a line only re-measures that line, and total_max() is O(1), so
+/// resizing a viewport after each keystroke doesn't rescan the buffer.
+///
+/// Internally a count of lines per width is kept (a BTreeMap), so an edit
+/// is O(log n) plus measuring the edited line.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let mut widths = LineWidths::new("ab\n你好你好\nH🧑🌾");
+///
+///     println!("total_max {}", widths.total_max()); // Prints 8
+///     widths.on_edit(1, "hi");
+///     println!("total_max {}", widths.total_max()); // Prints 5, now H🧑🌾 is the widest
+/// }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct LineWidths {
+    widths: Vec<usize>,             // string_width of each line
+    counts: BTreeMap<usize, usize>, // width -> number of lines that wide
+    max: usize,
+}
+
+impl LineWidths {
+    /// Measure every `\n` delimited line of text
+    pub fn new(text: &str) -> Self {
+        let mut line_widths = LineWidths::default();
+        for (line, st) in text.lines().enumerate() {
+            line_widths.on_edit(line, st);
+        }
+        line_widths
+    }
+
+    /// Replace the text of one line
+    ///
+    /// Editing past the last line adds the line (and any empty lines
+    /// before it).
+    pub fn on_edit(&mut self, line: usize, new_text: &str) {
+        if line >= self.widths.len() {
+            let added = line + 1 - self.widths.len();
+            self.widths.resize(line + 1, 0);
+            *self.counts.entry(0).or_insert(0) += added;
+        }
+        let old = std::mem::replace(&mut self.widths[line], string_width(new_text));
+        if let Some(count) = self.counts.get_mut(&old) {
+            *count -= 1;
+            if *count == 0 {
+                self.counts.remove(&old);
+            }
+        }
+        *self.counts.entry(self.widths[line]).or_insert(0) += 1;
+        self.max = self.counts.last_key_value().map_or(0, |(width, _)| *width);
+    }
+
+    /// Width of the widest line
+    pub fn total_max(&self) -> usize {
+        self.max
+    }
+
+    /// Width of one line, 0 if it's past the end
+    pub fn width(&self, line: usize) -> usize {
+        self.widths.get(line).copied().unwrap_or(0)
+    }
+
+    /// Number of lines
+    pub fn len(&self) -> usize {
+        self.widths.len()
+    }
+
+    /// True when there are no lines
+    pub fn is_empty(&self) -> bool {
+        self.widths.is_empty()
+    }
+}
+
+/// Iterate Graphemes with their Line Number and Column, for Diagnostics
+///
+/// Yields (line, col, grapheme), line is base 0 and col is the display
+/// column within the line.  The `\n` (or `\r\n`) ending a line is
+/// yielded at the end of its line, the next grapheme starts line + 1 at
+/// column 0.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "a你\nb好c";
+///
+///     println!("{:?}", located_graphemes(st).last()); // Prints Some((1, 3, "c"))
+/// }
+/// ```
+pub fn located_graphemes(st: &str) -> impl Iterator<Item = (usize, usize, &str)> {
+    let mut line = 0;
+    let mut col = 0;
+    st.graphemes(true).map(move |grapheme| {
+        let located = (line, col, grapheme);
+        if grapheme == "\n" || grapheme == "\r\n" {
+            line += 1;
+            col = 0;
+        } else {
+            col += cluster_width(grapheme);
+        }
+        located
+    })
+}
+
+/// Longest Run of Graphemes Found in Both Strings
+///
+/// Returns a slice of a.  Graphemes are compared whole, so an emoji or
+/// an accented letter is never split to make a match.  When there is a
+/// tie, the run found first in a wins.
+///
+/// Note: This is a simple O(a * b) table walk, fine for labels and lines.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "abc😊de".to_string();
+///
+///     println!("{}", longest_common_grapheme_substring(&st, "x😊dy")); // Prints 😊d
+/// }
+/// ```
+pub fn longest_common_grapheme_substring<'a>(a: &'a str, b: &str) -> &'a str {
+    let a_graphemes: Vec<(usize, &str)> = a.grapheme_indices(true).collect();
+    let b_graphemes: Vec<&str> = b.graphemes(true).collect();
+
+    // prev[j + 1] is the length of the common run ending at the previous
+    // a grapheme and b_graphemes[j]
+    let mut prev = vec![0; b_graphemes.len() + 1];
+    let mut curr = vec![0; b_graphemes.len() + 1];
+    let (mut best_len, mut best_end) = (0, 0);
+    for (i, (_, ga)) in a_graphemes.iter().enumerate() {
+        for (j, gb) in b_graphemes.iter().enumerate() {
+            curr[j + 1] = if ga == gb { prev[j] + 1 } else { 0 };
+            if curr[j + 1] > best_len {
+                best_len = curr[j + 1];
+                best_end = i + 1;
+            }
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+    if best_len == 0 {
+        return "";
+    }
+    let start = a_graphemes[best_end - best_len].0;
+    let end = a_graphemes.get(best_end).map_or(a.len(), |(idx, _)| *idx);
+    &a[start..end]
+}
+
+/// Mask Every Grapheme with mask, One mask per Column
+///
+/// Each grapheme becomes mask repeated to its width: a 2 column emoji
+/// becomes 2 masks, an accented letter 1, a zero width grapheme none.
+/// With a 1 column mask the result has the same width as st.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+///
+///     println!("{}", mask_graphemes(&st, '*')); // Prints ****************** (18 of them)
+///     println!("{}", mask_graphemes("a🧑", '#')); // Prints ###
+/// }
+/// ```
+pub fn mask_graphemes(st: &str, mask: char) -> String {
+    st.graphemes(true)
+        .flat_map(|grapheme| std::iter::repeat_n(mask, cluster_width(grapheme)))
+        .collect()
+}
+
+/// Mask a String, Keeping its Display Width
+///
+/// Returns mask repeated to cover the same number of columns as st, for
+/// password fields or redaction that mustn't shift the layout.  A wide
+/// mask is never allowed to overshoot, any leftover column is a space.
+///
+/// Note: A zero width mask can't cover anything, the result is all spaces.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     println!("[{}]", mask_to_width("你好", "*")); // Prints [****]
+///     println!("[{}]", mask_to_width("你好a", "●")); // Prints [●●●●●]
+///     println!("[{}]", mask_to_width("abc", "你")); // Prints [你 ]
+/// }
+/// ```
+pub fn mask_to_width(st: &str, mask: &str) -> String {
+    let width = string_width(st);
+    let mask_width = string_width(mask);
+    let count = width.checked_div(mask_width).unwrap_or(0);
+    let mut out = mask.repeat(count);
+    out.extend(std::iter::repeat_n(' ', width - count * mask_width));
+    out
+}
+
+/// Grapheme Boundary at or Before idx, from a Prebuilt Offset Table
+///
+/// Gives the same answer as grapheme_idx_at_idx with a binary search, for
+/// callers that already have the boundaries and don't want to segment the
+/// string again.
+///
+/// Note: offsets must be sorted and hold every grapheme start plus st.len(),
+///       like grapheme_offsets_with_base(st, 0) with st.len() pushed on the
+///       end.  An empty table returns 0.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+///     let mut offsets = grapheme_offsets_with_base(&st, 0);
+///     offsets.push(st.len());
+///
+///     println!("{}", nearest_boundary_in(&offsets, 10)); // Prints 6
+///     println!("{}", nearest_boundary_in(&offsets, 99)); // Prints 47
+/// }
+/// ```
+pub fn nearest_boundary_in(offsets: &[usize], idx: usize) -> usize {
+    match offsets.binary_search(&idx) {
+        Ok(at) => offsets[at],
+        Err(0) => offsets.first().copied().unwrap_or(0),
+        Err(at) => offsets[at - 1],
+    }
+}
+
+/// Nearest Grapheme Boundary to idx, Searching at most max_dist Bytes
+///
+/// Looks outward from idx in both directions and returns the closest
+/// boundary, preferring the earlier one on a tie.  Unlike
+/// grapheme_idx_at_idx the work is capped, so a pathological run of
+/// combining marks can't force a long scan; None means no boundary was
+/// found within max_dist bytes.  An idx past the end is treated as st.len().
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+///
+///     println!("{:?}", nearest_boundary_within(&st, 18, 0)); // Prints Some(18)
+///     println!("{:?}", nearest_boundary_within(&st, 8, 4)); // Prints Some(6)
+///     println!("{:?}", nearest_boundary_within(&st, 12, 3)); // Prints None
+/// }
+/// ```
+pub fn nearest_boundary_within(st: &str, idx: usize, max_dist: usize) -> Option<usize> {
+    let idx = idx.min(st.len());
+    let mut cursor = GraphemeCursor::new(0, st.len(), true);
+    let mut is_boundary = |pos: usize| {
+        if !st.is_char_boundary(pos) {
+            return false;
+        }
+        cursor.set_cursor(pos);
+        cursor.is_boundary(st, 0).unwrap_or(false)
+    };
+
+    for dist in 0..=max_dist {
+        if dist <= idx && is_boundary(idx - dist) {
+            return Some(idx - dist);
+        }
+        if idx + dist <= st.len() && is_boundary(idx + dist) {
+            return Some(idx + dist);
+        }
+        if dist > idx && idx + dist > st.len() {
+            break;
+        }
+    }
+    None
+}
+
+/// Next Grapheme from Current Index
+///
+/// ```rust
+/// use grapheme_utils::*;
+/// 
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+/// 
+///     println!("next_grapheme_from_idx {}", next_grapheme_from_idx(&st, 18)); // Prints 🧑
+/// }
+/// ```
+pub fn next_grapheme_from_idx(st: &str, idx: usize) -> &str {
+    let st_len = st.len();
+    if idx >= st_len {
+        return "";
+    }
+    let pos = next_grapheme_idx_from_idx(st, idx);
+    st[pos..].graphemes(true).next().unwrap_or("")
+}
+
+/// Byte Index of the Next Extended Grapheme from Current Index
+///
+/// NOTE: This can return the st.len(), meaning an illegal index
+///       if this is the last Grapheme in the string!
+///
+/// Note: In testing, The Rust library currently divides
+///       some characters that should be singular "🧑🌾"
+///
+/// Note: This code is inefficient...  small, but inefficient.
+///
+/// Note: This function has been modified to be panic proof.
+///       The underlying library can panic:
+///       unicode-segmentation-1.12.0/src/grapheme.rs:787:29:
+///
+/// ```rust
+/// use grapheme_utils::*;
+/// 
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+/// 
+///     println!("next_grapheme_from_idx {}", next_grapheme_from_idx(&st, 18)); // Prints 🧑
+/// }
+/// ```
+// Note: next_boundary is written to only require
+//       a short chunk of the string instead of the
+//       whole thing:
+//           next_boundary(st[beg..beg+ch.len_utf8+etc], beg)
+//
+//       next_boundary can then send an error back saying if
+//       it needs more (GraphemeIncomplete::NextChunk)
+//
+//       It may or may not to land on exact utf8 char
+//       boundaries, but it's really hard for prev_grapheme_idx
+//       where you need to know the exact info we're wanting.
+//
+pub fn next_grapheme_idx_from_idx(st: &str, idx: usize) -> usize {
+    let st_len = st.len();
+    if idx >= st_len {
+        return st_len;
+    }
+    let mut pos = idx;
+    while pos > 0 && (st.as_bytes()[pos] & 0xc0) == 0x80 {
+        pos -= 1;
+    }
+    let mut cursor = GraphemeCursor::new(pos, st_len, true);
+    cursor.next_boundary(st, 0).ok().flatten().unwrap_or(st_len)
+}
+
+/// nth Grapheme
+///
+/// Note, this will return the st.len() index if it would be
+///       past the end of the string even if the string
+///       is empty.
+///
+/// ```rust
+/// use grapheme_utils::*;
+/// 
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+/// 
+///     println!("nth_grapheme {}", nth_grapheme(&st, 1)); // Prints न्दी, the 2nd byte in the string, base 0
+///     println!("nth_grapheme {}", nth_grapheme(&st, 2)); // Prints H, the 3rd bytes in the string, base 0
+/// }
+/// ```
+// UUGH - Full Iter to nth!
+//        (GraphemeMap keeps the offsets for repeated lookups)
+//
+pub fn nth_grapheme(st: &str, nth: usize) -> &str {
+    UnicodeSegmentation::grapheme_indices(st, true)
+        .nth(nth)
+        .map(|(_, g)| g)
+        .unwrap_or_else(|| "")
+}
+
+/// nth Grapheme Counting from the End
+///
+/// n = 0 is the last grapheme, n = 1 the one before it, etc.
+/// Returns "" when n is out of range.
+///
+/// This walks backwards from the end, so it's cheap for small n.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+///
+///     println!("nth_grapheme_from_end {}", nth_grapheme_from_end(&st, 0)); // Prints é
+///     println!("nth_grapheme_from_end {}", nth_grapheme_from_end(&st, 11)); // Prints हि
+/// }
+/// ```
+pub fn nth_grapheme_from_end(st: &str, n: usize) -> &str {
+    st.graphemes(true).rev().nth(n).unwrap_or("")
+}
+
+/// nth Grapheme Index from nth
+///
+/// Note, this will return the st.len() index if it would be
+///       past the end of the string even if the string
+///       is empty.
+///
+/// ```rust
+/// use grapheme_utils::*;
+/// 
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+/// 
+///     println!("nth_grapheme_idx {}", nth_grapheme_idx(&st, 1)); // Prints 6 (index 6)
+///     println!("nth_grapheme_idx {}", nth_grapheme_idx(&st, 2)); // Prints 18 (index 18)
+/// }
+/// ```
+// Uugh - Full Iter!
+//
+pub fn nth_grapheme_idx(st: &str, nth: usize) -> usize {
+    UnicodeSegmentation::grapheme_indices(st, true)
+        .nth(nth)
+        .map(|(idx, _)| idx)
+        .unwrap_or_else(|| st.len())
+}
+
+/// UTF-16 Offset of the nth Grapheme
+///
+/// Returns the UTF-16 code unit offset where grapheme n starts, for
+/// bridging to UTF-16 APIs (like LSP positions).  Scalars above U+FFFF,
+/// most emoji, take 2 code units (a surrogate pair).
+///
+/// Note: n is clamped, past the end returns the UTF-16 length of st.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+///
+///     println!("nth_grapheme_to_utf16 {}", nth_grapheme_to_utf16(&st, 3)); // Prints 7, 🧑
+///     println!("nth_grapheme_to_utf16 {}", nth_grapheme_to_utf16(&st, 4)); // Prints 9, 🌾
+/// }
+/// ```
+pub fn nth_grapheme_to_utf16(st: &str, n: usize) -> usize {
+    st[..nth_grapheme_idx(st, n)].encode_utf16().count()
+}
+
+/// nth Grapheme Width
+///
+/// ```rust
+/// use grapheme_utils::*;
+/// 
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+/// 
+///     println!("nth grapheme_width {}", nth_grapheme_width(&st, 1)); // Prints 3
+///     println!("nth grapheme_width {}", nth_grapheme_width(&st, 2)); // Prints 1
+/// }
+/// ```
+pub fn nth_grapheme_width(st: &str, nth: usize) -> usize {
+    cluster_width(nth_grapheme(st, nth))
+}
+
+/// Num Graphemes In &str
+///
+/// Note, this will return the st.len() index if it would be
+///       past the end of the string even if the string
+///       is empty.
+/// ```rust
+/// use grapheme_utils::*;
+/// 
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+/// 
+///     println!("num_graphemes {}", num_graphemes(&st)); // Prints 12, the string has 12 grapheme clusters total
+/// }
+/// ```
+pub fn num_graphemes(st: &str) -> usize {
+    #[cfg(feature = "skip-bom")]
+    let st = strip_bom(st).0;
+    UnicodeSegmentation::grapheme_indices(st, true).count()
+}
+
+/// A Grapheme Boundary in Bytes, UTF-16 Code Units and Chars, see offset_table
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClusterOffsets {
+    pub byte: usize,  // utf8 byte offset, what this crate uses
+    pub utf16: usize, // UTF-16 code units, what LSP uses
+    pub char: usize,  // Unicode scalar values (chars)
+}
+
+/// Offset Translation Table, One Entry per Grapheme Boundary
+///
+/// Editors that juggle bytes, UTF-16 (LSP) and chars at the same time can
+/// precompute all three once.  There's an entry for the start of every
+/// grapheme, plus a final entry for the end of the string, so an empty
+/// string still has the single entry (0, 0, 0).
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "H🧑e".to_string();
+///
+///     for offsets in offset_table(&st) {
+///         println!("{} {} {}", offsets.byte, offsets.utf16, offsets.char);
+///     }
+///     // Prints
+///     // 0 0 0
+///     // 1 1 1
+///     // 5 3 2
+///     // 6 4 3
+/// }
+/// ```
+pub fn offset_table(st: &str) -> Vec<ClusterOffsets> {
+    let mut table = Vec::new();
+    let mut offsets = ClusterOffsets {
+        byte: 0,
+        utf16: 0,
+        char: 0,
+    };
+    for grapheme in st.graphemes(true) {
+        table.push(offsets);
+        offsets.byte += grapheme.len();
+        for ch in grapheme.chars() {
+            offsets.utf16 += ch.len_utf16();
+            offsets.char += 1;
+        }
+    }
+    table.push(offsets);
+    table
+}
+
+/// Columns by which a String Overflows max_width
+///
+/// Returns how much the string must shrink to fit, 0 when it already fits.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+///
+///     println!("overflow_width {}", overflow_width(&st, 10)); // Prints 8
+///     println!("overflow_width {}", overflow_width(&st, 20)); // Prints 0
+/// }
+/// ```
+pub fn overflow_width(st: &str, max_width: usize) -> usize {
+    string_width(st).saturating_sub(max_width)
+}
+
+/// Pad with fill up to target_width Display Columns
+///
+/// `{:<10}` pads by char count, which is wrong for CJK and emoji, this
+/// pads by string_width.  The width of fill counts too, a 2 column fill
+/// never overshoots, an odd column left over stays unfilled.  A string
+/// already at or past target_width is returned unchanged.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     println!("[{}]", pad_to_width("你好", 6, '.')); // Prints [你好..]
+///     println!("[{}]", pad_to_width("你好", 7, '　')); // Prints [你好　], 1 column short
+/// }
+/// ```
+pub fn pad_to_width(st: &str, target_width: usize, fill: char) -> String {
+    let mut out = st.to_string();
+    push_fill(
+        &mut out,
+        target_width.saturating_sub(string_width(st)),
+        fill,
+    );
+    out
+}
+
+/// Total Fill Columns Needed to Pad Every Item to the Widest
+///
+/// The sum of max width minus each item's width, to compare a ragged
+/// layout against an aligned one.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     println!("{}", padding_waste(&["a", "你", "hello"])); // Prints 7
+/// }
+/// ```
+pub fn padding_waste(items: &[&str]) -> usize {
+    let widths: Vec<usize> = items.iter().map(|item| string_width(item)).collect();
+    let max_width = widths.iter().copied().max().unwrap_or(0);
+    widths.iter().map(|width| max_width - width).sum()
+}
+
+/// Previoius Grapheme from current idx
+///
+/// ```rust
+/// use grapheme_utils::*;
+/// 
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+/// 
+///     println!("prev_grapheme_from_idx {}", prev_grapheme_from_idx(&st, 18)); // Prints न्दी
+/// }
+/// ```
+pub fn prev_grapheme_from_idx(st: &str, idx: usize) -> &str {
+    if idx == 0 {
+        return "";
+    }
+    let pos = prev_grapheme_idx_from_idx(st, idx);
+    grapheme_at_idx(st, pos)
+}
+
+// Chunk size prev_grapheme_idx_from_idx hands to GraphemeCursor
+const PREV_CHUNK_LEN: usize = 64;
+
+// Start of the chunk of at most PREV_CHUNK_LEN bytes ending at end
+fn prev_chunk_start(st: &str, end: usize) -> usize {
+    let mut beg = end.saturating_sub(PREV_CHUNK_LEN);
+    while !st.is_char_boundary(beg) {
+        beg -= 1;
+    }
+    beg
+}
+
+/// Byte Index of the Previous Extended Grapheme from Current Idx
+///
+/// NOTE: This will return 0, even when the string is empty.
+///
+/// Note: In testing, The Rust library currently divides
+///       some characters that should be singular "🧑🌾"
+///
+/// Note: This function has been modified to be panic proof.
+///       The underlying library can panic:
+///       unicode-segmentation-1.12.0/src/grapheme.rs:787:29:
+///
+/// ```rust
+/// use grapheme_utils::*;
+/// 
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+/// 
+///     println!(
+///         "prev_grapheme_idx_from_idx {}",
+///         prev_grapheme_idx_from_idx(&st, 18)
+///     ); // Prints 6
+/// }
+/// ```
+// Note: prev_boundary only needs a short chunk of the
+//       string ending at idx instead of the whole thing:
+//           prev_boundary(&st[beg..pos], beg)
+//
+//       When the cluster runs past beg it answers
+//       GraphemeIncomplete::PrevChunk and we hand it the
+//       chunk before, or PreContext when it needs to look
+//       further back (regional indicator pairs, etc).
+//
+//       beg MUST land on exact utf8 char boundaries, so it
+//       is walked back until st.is_char_boundary(beg).
+pub fn prev_grapheme_idx_from_idx(st: &str, idx: usize) -> usize {
+    let st_len = st.len();
+    if st_len == 0 {
+        return 0;
+    }
+
+    let max_len = st_len.saturating_sub(1);
+
+    let mut pos = idx;
+    while pos <= max_len && (st.as_bytes()[pos] & 0xc0) == 0x80 {
+        pos += 1;
+    }
+    if pos > st_len {
+        pos = st_len;
+    }
+
+    if pos == 0 {
+        return 0;
+    }
+
+    let mut cursor = GraphemeCursor::new(pos, st_len, true);
+    let mut chunk_end = pos;
+    let mut chunk_start = prev_chunk_start(st, chunk_end);
+    loop {
+        match cursor.prev_boundary(&st[chunk_start..chunk_end], chunk_start) {
+            Ok(Some(prev)) => return prev,
+            Err(GraphemeIncomplete::PrevChunk) => {
+                chunk_end = chunk_start;
+                chunk_start = prev_chunk_start(st, chunk_end);
+            }
+            Err(GraphemeIncomplete::PreContext(ctx_end)) => {
+                let ctx_start = prev_chunk_start(st, ctx_end);
+                cursor.provide_context(&st[ctx_start..ctx_end], ctx_start);
+            }
+            _ => return 0, // If we can't find a valid breakpoint or are at the start, return 0
+        }
+    }
+}
+
+/// Share total_cells Among Items in Proportion to their Display Width
+///
+/// Largest remainder apportionment: each item gets the whole part of its
+/// share, then the cells left over go to the largest remainders (earlier
+/// items first on a tie), so the result always adds up to total_cells.
+/// If every item is zero width they share equally.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     println!("{:?}", proportional_cells(&["a", "你", "abc"], 12)); // Prints [2, 4, 6]
+///     println!("{:?}", proportional_cells(&["a", "b", "c"], 10)); // Prints [4, 3, 3]
+/// }
+/// ```
+pub fn proportional_cells(items: &[&str], total_cells: usize) -> Vec<usize> {
+    let mut weights: Vec<usize> = items.iter().map(|item| string_width(item)).collect();
+    if weights.iter().all(|&weight| weight == 0) {
+        weights.fill(1);
+    }
+    let total_weight: usize = weights.iter().sum();
+    if total_weight == 0 {
+        return Vec::new();
+    }
+
+    let mut cells: Vec<usize> = weights
+        .iter()
+        .map(|weight| total_cells * weight / total_weight)
+        .collect();
+    let mut by_remainder: Vec<usize> = (0..weights.len()).collect();
+    by_remainder.sort_by_key(|&i| std::cmp::Reverse(total_cells * weights[i] % total_weight));
+    let left_over = total_cells - cells.iter().sum::<usize>();
+    for &i in by_remainder.iter().take(left_over) {
+        cells[i] += 1;
+    }
+    cells
+}
+
+/// Read a Stream in Chunks that Never Split a Grapheme
+///
+/// Reads roughly chunk_bytes at a time and yields Strings of whole
+/// graphemes.  The last grapheme read is held back until more input shows
+/// whether it continues (a combining mark may still follow), so a chunk
+/// can be a bit shorter, or when one grapheme is huge, longer, than
+/// chunk_bytes.  Everything left is yielded at the end of the stream.
+///
+/// Note: Invalid UTF-8 yields an io::ErrorKind::InvalidData error after
+///       the valid text before it, and then the iterator ends.
+///
+/// Note: Requires the `std` feature.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let input = "e\u{301}e\u{301}e\u{301}".as_bytes();
+///
+///     for chunk in read_grapheme_chunks(input, 2) {
+///         println!("{:?}", chunk.unwrap()); // Prints "e\u{301}" 3 times, never a lone "e"
+///     }
+/// }
+/// ```
+#[cfg(feature = "std")]
+pub fn read_grapheme_chunks<R: Read>(
+    mut reader: R,
+    chunk_bytes: usize,
+) -> impl Iterator<Item = io::Result<String>> {
+    let chunk_bytes = chunk_bytes.max(1);
+    let mut pending: Vec<u8> = Vec::new();
+    let mut want = chunk_bytes;
+    let mut eof = false;
+    std::iter::from_fn(move || {
+        loop {
+            while !eof && pending.len() < want {
+                let requested = want - pending.len();
+                match reader
+                    .by_ref()
+                    .take(requested as u64)
+                    .read_to_end(&mut pending)
+                {
+                    Ok(n) => eof = n < requested,
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+
+            let (valid, bad) = match std::str::from_utf8(&pending) {
+                Ok(text) => (text.len(), false),
+                Err(e) => (e.valid_up_to(), eof || e.error_len().is_some()),
+            };
+            let text = std::str::from_utf8(&pending[..valid]).unwrap_or("");
+            let cut = if eof || bad {
+                valid
+            } else {
+                text.grapheme_indices(true)
+                    .next_back()
+                    .map_or(0, |(idx, _)| idx)
+            };
+
+            if cut > 0 {
+                want = chunk_bytes;
+                let chunk = String::from_utf8(pending.drain(..cut).collect());
+                return Some(Ok(chunk.unwrap_or_default()));
+            }
+            if bad {
+                pending.clear();
+                eof = true;
+                return Some(Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "stream did not contain valid UTF-8",
+                )));
+            }
+            if eof {
+                return None;
+            }
+            // A single grapheme so far, read more before cutting
+            want = pending.len() + chunk_bytes;
+        }
+    })
+}
+
+/// Rewrap Paragraphs to a New Width, the Classic fmt / gq
+///
+/// A single `\n` is soft, the lines of a paragraph are joined and rewrapped
+/// on whitespace so no line is wider than width.  Blank lines are hard
+/// paragraph breaks and are kept as they are.
+///
+/// Note: A word wider than width is never split, it gets a line of its own.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "aaa bb\ncc d\n\n你好 x";
+///
+///     println!("{:?}", reflow(st, 6)); // Prints "aaa bb\ncc d\n\n你好 x"
+///     println!("{:?}", reflow(st, 4)); // Prints "aaa\nbb\ncc d\n\n你好\nx"
+/// }
+/// ```
+pub fn reflow(st: &str, width: usize) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    let mut line_width = 0;
+    for text_line in st.lines() {
+        if text_line.trim().is_empty() {
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+                line_width = 0;
+            }
+            lines.push(String::new());
+            continue;
+        }
+        for word in text_line.split_whitespace() {
+            let word_width = string_width(word);
+            if !line.is_empty() && line_width + 1 + word_width > width {
+                lines.push(std::mem::take(&mut line));
+                line_width = 0;
+            }
+            if !line.is_empty() {
+                line.push(' ');
+                line_width += 1;
+            }
+            line.push_str(word);
+            line_width += word_width;
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+
+    let mut out = lines.join("\n");
+    if st.ends_with('\n') {
+        out.push('\n');
+    }
+    out
+}
+
+/// Override the Width of a Scalar
+///
+/// unicode-width's tables lag behind newly assigned emoji, so some recent
+/// emoji report width 1 while terminals draw them 2 columns wide.  This
+/// patches the width until the dependency catches up.
+///
+/// A grapheme whose first (base) scalar has an override is measured as the
+/// override width, modifiers and joiners included.  string_width and every
+/// other width function in this crate honor the overrides, including the
+/// per scalar widths of decompose_grapheme.  An overridden grapheme no
+/// longer counts for has_ambiguous_width.
+///
+/// Note: The table is global and thread safe (a RwLock), registering from
+///       any thread affects every thread.  Register once at startup, the
+///       lock is skipped entirely until the first override is registered.
+///
+/// Note: Requires the `width-override` feature.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     println!("string_width {}", string_width("ⓐb")); // Prints 2
+///     register_width_override('ⓐ', 2);
+///     println!("string_width {}", string_width("ⓐb")); // Prints 3
+/// }
+/// ```
+#[cfg(feature = "width-override")]
+pub fn register_width_override(scalar: char, width: usize) {
+    let mut table = WIDTH_OVERRIDES.write().unwrap_or_else(|e| e.into_inner());
+    table.insert(scalar, width);
+    HAS_WIDTH_OVERRIDES.store(true, Ordering::Release);
+}
+
+/// Repair a Stored Cursor After the Buffer Changed Underneath it
+///
+/// Returns a valid grapheme boundary at or before old_byte, the start of
+/// the grapheme it lands in, like most editors do.  A stale offset past the
+/// end becomes st.len().  Never panics, even mid UTF-8 sequence.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+///
+///     println!("{}", repair_cursor(&st, 10)); // Prints 6, the start of न्दी
+///     println!("{}", repair_cursor(&st, 500)); // Prints 47
+/// }
+/// ```
+pub fn repair_cursor(st: &str, old_byte: usize) -> usize {
+    grapheme_idx_at_idx(st, old_byte)
+}
+
+/// Width Required to Show a Block of Text Without Wrapping
+///
+/// Returns the string_width of the widest line.  Lines are split on
+/// `\n` (a trailing `\r` is dropped, so `\r\n` works too).
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "हिन्दी\nH🧑🌾e\n‘︀o‘︁réé".to_string();
+///
+///     println!("required_width {}", required_width(&st)); // Prints 7, the width of ‘︀o‘︁réé
+/// }
+/// ```
+pub fn required_width(st: &str) -> usize {
+    st.lines().map(string_width).max().unwrap_or(0)
+}
+
+/// Iterate Graphemes from the End, with their Column from the Right
+///
+/// Yields (column_from_right, grapheme), starting with the last grapheme.
+/// column_from_right is how far the grapheme's left edge is from the right
+/// edge of the text, so for right-aligned text ending at column `right`
+/// each grapheme is drawn at `right - column_from_right`.
+///
+/// No need to know the total width up front.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "ab你".to_string();
+///
+///     for (col, grapheme) in rev_grapheme_col_iter(&st) {
+///         println!("{} {}", col, grapheme);
+///     }
+///     // Prints
+///     // 2 你
+///     // 3 b
+///     // 4 a
+/// }
+/// ```
+pub fn rev_grapheme_col_iter(st: &str) -> impl Iterator<Item = (usize, &str)> {
+    st.graphemes(true).rev().scan(0, |col, grapheme| {
+        *col += cluster_width(grapheme);
+        Some((*col, grapheme))
+    })
+}
+
+/// Wrapped Row Number the Grapheme at idx is Shown On
+///
+/// st is wrapped at term_width like changed_rows does, forced `\n` breaks
+/// included, for placing a scrollbar thumb.  A `\n` is on the row it
+/// ends, and an idx past the end is on the last row.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "abcdefghij";
+///
+///     println!("{}", row_of_idx(st, 4, 9)); // Prints 2, the rows are abcd, efgh and ij
+///     println!("{}", row_of_idx("ab\ncd", 4, 3)); // Prints 1
+/// }
+/// ```
+pub fn row_of_idx(st: &str, term_width: usize, idx: usize) -> usize {
+    let pos = grapheme_idx_at_idx(st, idx);
+    wrapped_row_ranges(st, term_width)
+        .iter()
+        .rposition(|&(start, _)| start <= pos)
+        .unwrap_or(0)
+}
+
+/// Split into parts Pieces of About Equal Display Width
+///
+/// Graphemes can't be split and have different widths, so exact halves or
+/// thirds usually aren't possible.  This is greedy: the cut for piece k
+/// goes on the grapheme boundary whose running width is closest to
+/// k * width / parts (the earlier one on a tie).  Always returns parts
+/// pieces, some may be empty when there are too few graphemes.  A parts of
+/// 0 is treated as 1.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+///
+///     let pieces = split_balanced_by_width(&st, 3);
+///     println!("{:?}", pieces.iter().map(|p| string_width(p)).collect::<Vec<_>>());
+///     // Prints [6, 6, 6]
+/// }
+/// ```
+pub fn split_balanced_by_width(st: &str, parts: usize) -> Vec<&str> {
+    let parts = parts.max(1);
+    let mut bounds = vec![(0, 0)];
+    let mut col = 0;
+    for (idx, grapheme) in st.grapheme_indices(true) {
+        col += cluster_width(grapheme);
+        bounds.push((idx + grapheme.len(), col));
+    }
+    let total = col;
+    let off_by = |bound: (usize, usize), k: usize| (bound.1 * parts).abs_diff(total * k);
+
+    let mut pieces = Vec::with_capacity(parts);
+    let mut at = 0;
+    let mut start = 0;
+    for k in 1..parts {
+        while at + 1 < bounds.len() && off_by(bounds[at + 1], k) < off_by(bounds[at], k) {
+            at += 1;
+        }
+        pieces.push(&st[start..bounds[at].0]);
+        start = bounds[at].0;
+    }
+    pieces.push(&st[start..]);
+    pieces
+}
+
+/// Return the string_width
+///
+/// ```rust
+/// use grapheme_utils::*;
+/// 
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+/// 
+///     println!("string_width {}", string_width(&st)); // Prints 18, the string uses 18 columns
+/// }
+/// ```
+pub fn string_width(st: &str) -> usize {
+    #[cfg(feature = "skip-bom")]
+    let st = strip_bom(st).0;
+    grapheme_spans(st).map(|(_, _, width)| width).sum()
+}
+
+/// Return the string_width, Drawing Every Grapheme at Least 1 Column Wide
+///
+/// Some monospace renderers give zero width graphemes (a lone combining
+/// mark, a zero width space) a visible cell anyway.  This counts every
+/// grapheme as max(1, width).
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "\u{301}a你".to_string();
+///
+///     println!("string_width {}", string_width(&st)); // Prints 3
+///     println!("string_width_min_one {}", string_width_min_one(&st)); // Prints 4
+/// }
+/// ```
+pub fn string_width_min_one(st: &str) -> usize {
+    st.graphemes(true)
+        .map(|grapheme| cluster_width(grapheme).max(1))
+        .sum()
+}
+
+/// string_width with a Per Grapheme Scale Factor
+///
+/// Sums scale(grapheme) * width for every grapheme, for experimental
+/// layouts where some graphemes count fractionally, emoji as 1.5 cells for
+/// example.  A scale of |_| 1.0 gives string_width as an f64.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "a你😀".to_string();
+///
+///     let scaled = string_width_scaled(&st, |g| if g == "😀" { 0.75 } else { 1.0 });
+///     println!("{}", scaled); // Prints 4.5
+/// }
+/// ```
+pub fn string_width_scaled(st: &str, scale: impl Fn(&str) -> f64) -> f64 {
+    grapheme_spans(st)
+        .map(|(_, grapheme, width)| scale(grapheme) * width as f64)
+        .sum()
+}
+
+/// Strip a Leading Byte Order Mark
+///
+/// Files often start with a UTF-8 BOM (U+FEFF), which isn't a visible
+/// grapheme.  Returns the string without it, and whether one was there.
+///
+/// Note: With the `skip-bom` feature, num_graphemes and string_width
+///       ignore a leading BOM themselves.  Without it, the BOM counts as
+///       1 grapheme of width 0.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "\u{FEFF}H🧑🌾".to_string();
+///
+///     println!("{:?}", strip_bom(&st)); // Prints ("H🧑🌾", true)
+///     println!("{:?}", strip_bom("H🧑🌾")); // Prints ("H🧑🌾", false)
+/// }
+/// ```
+pub fn strip_bom(st: &str) -> (&str, bool) {
+    match st.strip_prefix('\u{FEFF}') {
+        Some(rest) => (rest, true),
+        None => (st, false),
+    }
+}
+
+// Byte index just past the ANSI escape sequence starting at st[start] (an ESC)
+//
+// Handles CSI (ESC [ ... final), OSC (ESC ] ... BEL or ESC \) and the two
+// byte ESC sequences.  An incomplete escape at the end of the string runs to
+// st.len(), so it is swallowed instead of being measured.
+//
+// Every delimiter is ascii, so the returned index is always a char boundary.
+#[cfg(feature = "ansi")]
+fn ansi_escape_end(st: &str, start: usize) -> usize {
+    let bytes = st.as_bytes();
+    let mut pos = start + 1;
+    match bytes.get(pos) {
+        Some(b'[') => {
+            pos += 1;
+            while pos < bytes.len() {
+                match bytes[pos] {
+                    0x40..=0x7e => return pos + 1, // Final byte
+                    0x20..=0x3f => pos += 1,       // Parameter or intermediate
+                    _ => return pos,               // Malformed, stop here
+                }
+            }
+            bytes.len()
+        }
+        Some(b']') => {
+            pos += 1;
+            while pos < bytes.len() {
+                match bytes[pos] {
+                    0x07 => return pos + 1,
+                    0x1b if bytes.get(pos + 1) == Some(&b'\\') => return pos + 2,
+                    _ => pos += 1,
+                }
+            }
+            bytes.len()
+        }
+        Some(0x40..=0x5f) => pos + 1,
+        _ => pos, // Lone ESC
+    }
+}
+
+// Walk SGR parameters left to right: "0;31" is open, "31;0" is reset.
+// The numbers after 38/48/58 (5;n or 2;r;g;b) are colors, not resets.
+#[cfg(feature = "ansi")]
+fn sgr_leaves_style_open(params: &str, mut style_open: bool) -> bool {
+    let mut params = params.split(';');
+    while let Some(param) = params.next() {
+        if param.trim_start_matches('0').is_empty() {
+            style_open = false;
+            continue;
+        }
+        style_open = true;
+        if matches!(param, "38" | "48" | "58") {
+            let skip = match params.next() {
+                Some("5") => 1,
+                Some("2") => 3,
+                _ => 0,
+            };
+            for _ in 0..skip {
+                params.next();
+            }
+        }
+    }
+    style_open
+}
+
+/// Truncate colored text to a display width, keeping ANSI escapes intact
+///
+/// Escape sequences are copied whole and take up no columns.  Graphemes are
+/// never split, so a wide grapheme that would overflow is left out.
+///
+/// If a style (SGR) is still open at the cut point, a reset (`\x1b[0m`) is
+/// appended so the color doesn't bleed into whatever follows.
+///
+/// Note: Requires the `ansi` feature.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "\x1b[31m你好\x1b[0m".to_string();
+///
+///     println!("{:?}", truncate_ansi_to_width(&st, 3)); // Prints "\u{1b}[31m你\u{1b}[0m"
+/// }
+/// ```
+#[cfg(feature = "ansi")]
+pub fn truncate_ansi_to_width(st: &str, max_width: usize) -> String {
+    let mut out = String::with_capacity(st.len());
+    let mut width = 0;
+    let mut style_open = false;
+    let mut pos = 0;
+    while pos < st.len() {
+        if st.as_bytes()[pos] == 0x1b {
+            let end = ansi_escape_end(st, pos);
+            let seq = &st[pos..end];
+            if let Some(params) = seq.strip_prefix("\x1b[").and_then(|s| s.strip_suffix('m')) {
+                style_open = sgr_leaves_style_open(params, style_open);
+            }
+            out.push_str(seq);
+            pos = end;
+            continue;
+        }
+        let text_end = st[pos..].find('\x1b').map_or(st.len(), |off| pos + off);
+        for grapheme in st[pos..text_end].graphemes(true) {
+            let w = cluster_width(grapheme);
+            if width + w > max_width {
+                if style_open {
+                    out.push_str("\x1b[0m");
+                }
+                return out;
+            }
+            width += w;
+            out.push_str(grapheme);
+        }
+        pos = text_end;
+    }
+    out
+}
+
+/// Prefix that Fits in total_width, Leaving reserve Columns Free
+///
+/// For a line with a right side widget: the prefix that fits in
+/// total_width - reserve columns, cut on a grapheme boundary (a wide
+/// grapheme that would straddle the edge is left off).  If reserve takes
+/// all of total_width, "" is returned.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+///
+///     println!("{}", truncate_reserving(&st, 18, 3)); // Prints हिन्दीH🧑🌾e‘︀o‘︁, 15 columns
+///     println!("[{}]", truncate_reserving(&st, 3, 3)); // Prints []
+/// }
+/// ```
+pub fn truncate_reserving(st: &str, total_width: usize, reserve: usize) -> &str {
+    if reserve >= total_width {
+        return "";
+    }
+    truncate_to_width(st, total_width - reserve)
+}
+
+/// Longest Prefix that Fits in max_width Columns
+///
+/// Cut on a grapheme boundary, never inside one.  If the next grapheme
+/// would overflow, like a 2 column 🧑 with 1 column left, the prefix stops
+/// before it and that column stays unused.  A slice of st, no allocation.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+///
+///     println!("{}", truncate_to_width(&st, 5)); // Prints हिन्दी
+///     println!("{}", truncate_to_width(&st, 7)); // Prints हिन्दीH, 🧑 doesn't fit
+/// }
+/// ```
+pub fn truncate_to_width(st: &str, max_width: usize) -> &str {
+    &st[..last_full_cluster_end(st, max_width)]
+}
+
+/// Would Truncating to max_width Cut Cleanly?
+///
+/// True if the string already fits, or if the cut at max_width lands
+/// exactly between graphemes.  False when a wide grapheme straddles the
+/// limit, so a truncated result falls short by a column, useful for
+/// deciding between an ellipsis and a partial-cell marker.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+///
+///     println!("{}", truncation_is_clean(&st, 5)); // Prints true, न्दी ends at 5
+///     println!("{}", truncation_is_clean(&st, 7)); // Prints false, 🧑 covers 6 and 7
+/// }
+/// ```
+pub fn truncation_is_clean(st: &str, max_width: usize) -> bool {
+    let mut col = 0;
+    for grapheme in st.graphemes(true) {
+        let width = cluster_width(grapheme);
+        if col + width > max_width {
+            return col == max_width;
+        }
+        col += width;
+    }
+    true
+}
+
+/// Each Distinct Grapheme in the String
+///
+/// In first seen order, or sorted when sorted is true.  Whole clusters are
+/// compared without normalization, so the precomposed and decomposed é are
+/// two different graphemes.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     println!("{:?}", unique_graphemes("banana", false)); // Prints ["b", "a", "n"]
+///     println!("{:?}", unique_graphemes("banana", true)); // Prints ["a", "b", "n"]
+/// }
+/// ```
+pub fn unique_graphemes(st: &str, sorted: bool) -> Vec<&str> {
+    if sorted {
+        return st
+            .graphemes(true)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+    }
+    let mut seen = BTreeSet::new();
+    st.graphemes(true).filter(|g| seen.insert(*g)).collect()
+}
+
+/// Grapheme Number Containing a UTF-16 Offset
+///
+/// The reverse of nth_grapheme_to_utf16, for turning an LSP position into
+/// a grapheme number.  An offset in the middle of a grapheme (between the
+/// halves of a surrogate pair for example) gives that grapheme.
+///
+/// Note: u16_idx is clamped, past the end returns num_graphemes(st).
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+///
+///     println!("{}", utf16_to_grapheme_number(&st, 7)); // Prints 3, 🧑
+///     println!("{}", utf16_to_grapheme_number(&st, 8)); // Prints 3, 🧑's low surrogate
+///     println!("{}", utf16_to_grapheme_number(&st, 99)); // Prints 12
+/// }
+/// ```
+pub fn utf16_to_grapheme_number(st: &str, u16_idx: usize) -> usize {
+    let mut u16_end = 0;
+    for (number, grapheme) in st.graphemes(true).enumerate() {
+        u16_end += grapheme.encode_utf16().count();
+        if u16_idx < u16_end {
+            return number;
+        }
+    }
+    num_graphemes(st)
+}
+
+/// Return the string_width, ignoring ANSI escape sequences
+///
+/// Color codes and other CSI/OSC escapes take up no columns on a terminal,
+/// so they are skipped and only the printable graphemes are measured.
+///
+/// Note: An incomplete escape at the end of the string is ignored.
+///
+/// Note: Requires the `ansi` feature.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "\x1b[31m你好\x1b[0m".to_string();
+///
+///     println!("string_width {}", string_width(&st)); // Prints 13, the escapes are counted
+///     println!("visible_width {}", visible_width(&st)); // Prints 4, only 你好 is counted
+/// }
+/// ```
+#[cfg(feature = "ansi")]
+pub fn visible_width(st: &str) -> usize {
+    let mut total = 0;
+    let mut pos = 0;
+    while let Some(off) = st[pos..].find('\x1b') {
+        let esc = pos + off;
+        total += string_width(&st[pos..esc]);
+        pos = ansi_escape_end(st, esc);
+    }
+    total + string_width(&st[pos..])
+}
+
+/// Do Two Strings Look the Same, Ignoring Zero Width Graphemes
+///
+/// The normalization is exactly: drop every grapheme whose width is 0
+/// (zero width spaces, stray joiners, BOMs, lone combining marks...), then
+/// compare the remaining graphemes byte for byte.  No unicode
+/// normalization is done, so the two forms of é are still different.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     println!("visually_equal {}", visually_equal("ab", "a\u{200B}b")); // Prints true
+///     println!("visually_equal {}", visually_equal("ab", "ba")); // Prints false
+/// }
+/// ```
+pub fn visually_equal(a: &str, b: &str) -> bool {
+    let visible = |grapheme: &&str| cluster_width(grapheme) > 0;
+    a.graphemes(true)
+        .filter(visible)
+        .eq(b.graphemes(true).filter(visible))
+}
+
+/// Line Number and Width of the Widest Line, in One Pass
+///
+/// Returns (line_index, width), lines split like required_width.  On a tie
+/// the first widest line wins.  An empty string gives (0, 0).
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "ab\n你好你\nabcdef";
+///
+///     println!("{:?}", widest_line(st)); // Prints (1, 6)
+/// }
+/// ```
+pub fn widest_line(st: &str) -> (usize, usize) {
+    let mut widest = (0, 0);
+    for (line_index, line) in st.lines().enumerate() {
+        let width = string_width(line);
+        if width > widest.1 {
+            widest = (line_index, width);
+        }
+    }
+    widest
+}
+
+/// Width st Would Have with the Bytes start..end Removed
+///
+/// Previews whether a deletion makes a line fit, without building the new
+/// string.  Both ends snap back to the start of their grapheme, like the
+/// other index functions.
+///
+/// Note: Graphemes on each side of the removed range are measured as they
+///       are now, if removing the range would join them into one grapheme
+///       the real width can differ.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+///
+///     println!("{}", width_after_removal(&st, 6, 18)); // Prints 15, न्दी is gone
+/// }
+/// ```
+pub fn width_after_removal(st: &str, start: usize, end: usize) -> usize {
+    let start = grapheme_idx_at_idx(st, start);
+    let end = grapheme_idx_at_idx(st, end).max(start);
+    string_width(st) - string_width(&st[start..end])
+}
+
+/// How Many Graphemes Have Each Width
+///
+/// Maps each width to the number of graphemes that wide, zero width
+/// graphemes are counted under 0.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+///
+///     println!("{:?}", width_histogram(&st)); // Prints {1: 7, 2: 4, 3: 1}
+/// }
+/// ```
+pub fn width_histogram(st: &str) -> BTreeMap<usize, usize> {
+    let mut histogram = BTreeMap::new();
+    for grapheme in st.graphemes(true) {
+        *histogram.entry(cluster_width(grapheme)).or_insert(0) += 1;
+    }
+    histogram
+}
+
+/// Run-Length Encoded Grapheme Widths
+///
+/// (width, count) for each run of graphemes with the same width, a
+/// compact layout cache: a long ascii string is a single (1, n).
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     println!("{:?}", width_rle("aaa你你")); // Prints [(1, 3), (2, 2)]
+/// }
+/// ```
+pub fn width_rle(st: &str) -> Vec<(usize, usize)> {
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    for grapheme in st.graphemes(true) {
+        let width = cluster_width(grapheme);
+        match runs.last_mut() {
+            Some((run_width, count)) if *run_width == width => *count += 1,
+            _ => runs.push((width, 1)),
+        }
+    }
+    runs
+}
+
+/// Does the String Width Change Between its NFC and NFD Forms?
+///
+/// For debugging alignment bugs where text was normalized somewhere along
+/// the way.  The unicode-width tables keep composed and decomposed forms
+/// the same width, so a true here usually points at a
+/// register_width_override entry for a precomposed scalar.
+///
+/// Note: Requires the `normalize` feature.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+///
+///     println!("{}", width_varies_by_normalization(&st)); // Prints false
+/// }
+/// ```
+#[cfg(feature = "normalize")]
+pub fn width_varies_by_normalization(st: &str) -> bool {
+    let nfc: String = st.nfc().collect();
+    let nfd: String = st.nfd().collect();
+    string_width(&nfc) != string_width(&nfd)
+}
+
+/// Byte Range of the Word at idx, for Double-Click Selection
+///
+/// Words follow the unicode_segmentation word bounds (the same words as
+/// unicode_words), and the range is always on grapheme boundaries.
+///
+/// Note: If idx is on whitespace or punctuation, or past the end, an
+///       empty range at idx (snapped to its grapheme) is returned.
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "hello wörld!".to_string();
+///
+///     println!("{:?}", word_range_at_idx(&st, 8)); // Prints (6, 12), wörld
+///     println!("{:?}", word_range_at_idx(&st, 5)); // Prints (5, 5), the space
+/// }
+/// ```
+pub fn word_range_at_idx(st: &str, idx: usize) -> (usize, usize) {
+    let pos = grapheme_idx_at_idx(st, idx);
+    for (start, word) in st.split_word_bound_indices() {
+        let end = start + word.len();
+        if pos < end {
+            if start <= pos && word.chars().any(char::is_alphanumeric) {
+                return (start, end);
+            }
+            break;
+        }
+    }
+    (pos, pos)
+}
+
+/// Write Grapheme (start, end) Byte Ranges into a Caller Provided Buffer
+///
+/// No allocation: up to out.len() ranges are written, and the number
+/// written is returned.  If the string has more graphemes than fit, out is
+/// filled and the rest are left off, so a return value of out.len() may
+/// mean truncation (compare with num_graphemes when it matters).
+///
+/// ```rust
+/// use grapheme_utils::*;
+///
+/// fn main() {
+///     let st = "हिन्दीH🧑🌾e‘︀o‘︁réé".to_string();
+///     let mut ranges = [(0, 0); 4];
+///
+///     let written = write_ranges(&st, &mut ranges);
+///     println!("{} {:?}", written, ranges); // Prints 4 [(0, 6), (6, 18), (18, 19), (19, 23)]
+/// }
+/// ```
+pub fn write_ranges(st: &str, out: &mut [(usize, usize)]) -> usize {
+    let mut written = 0;
+    for (slot, (idx, grapheme)) in out.iter_mut().zip(st.grapheme_indices(true)) {
+        *slot = (idx, idx + grapheme.len());
+        written += 1;
+    }
+    written
+}
+
+
+#[cfg(test)]
+#[allow(clippy::needless_range_loop, clippy::print_with_newline)]
+mod tests {
+    use super::*;
+    type TestData = (
+        usize, // testnum
+        usize, // pgifi: prev_grapheme_idx_from_idx
+        usize, // giati: grapheme_idx_at_idx
+        usize, // nxgifi: next_grapheme_idx_from_idx
+        &'static str, // pgfi: prev_grapheme_from_idx
+        &'static str, // gati: grapheme_at_idx
+        &'static str, // nxgfi: next_grapheme_from_idx
+        usize, // gwi: grapheme width_idx
+        usize, // glen: grapheme_len
+        &'static str, // nthg: nth_grapheme
+        usize, // ngw: nth_grapheme width
+        usize, // nthgi: nth_grapheme_idx
+        usize, // numg: num_graphemes
+        usize, // sw: string_width
+    );
+
+    fn run_grapheme_test(st: &str, expected: Vec<TestData>) {
+        // Note:  Testing An Error:
+        // The Character 🧑🌾 is supposed to be 1 Character.
+        // (Look at it in a real editor)
+        // I expect this to be fixed eventually, but it's here for now.
+        //
+        // Note, the 2 éé are differnt.
+        // First one from a French AZERTY keyboard ( utf8 bytes c3a9, or codepoint e9)
+        // Second from the standard linux Ctrl-U character entry: (utf8 bytes 65 cc 81, or codepoint-modifier 65 301)
+        let string_len = st.len();
+        println!("Testing grapheme vector for \"{}\"", st);
+        assert_eq!(expected.len(), string_len + 2); // Ensure there's one more expected result than the length of the input
+
+        for i in 0..string_len + 2 {
+            print!("Testing String: \"{}\", at byte index: {} \n", st, i);
+            assert_eq!(expected[i].0, i); // Position index
+            print!("i:{} ok, \n", i);
+
+            let pgifi = prev_grapheme_idx_from_idx(st, i);
+            print!("prev_grapheme_idx_from_idx");
+            assert_eq!(expected[i].1, pgifi);
+            println!("  ok:{}", pgifi);
+
+            let giati = grapheme_idx_at_idx(st, i);
+            print!("grapheme_idx_at_idx");
+            assert_eq!(expected[i].2, giati);
+            println!("  ok:{}", giati);
+
+            let nxgifi = next_grapheme_idx_from_idx(st, i);
+            print!("next_grapheme_idx_from_idx");
+            assert_eq!(expected[i].3, nxgifi);
+            println!("  ok:{}\n", nxgifi);
+
+            let pgfi = prev_grapheme_from_idx(st, i);
+            print!("prev_grapheme_from_idx");
+            assert_eq!(expected[i].4, pgfi);
+            println!("  ok:{}", pgfi);
+
+            let gati = grapheme_at_idx(st, i);
+            print!("grapheme_at_idx");
+            assert_eq!(expected[i].5, gati);
+            println!("  ok:{}", gati);
+
+            let nxgfi = next_grapheme_from_idx(st, i);
+            print!("next_grapheme_from_idx");
+            assert_eq!(expected[i].6, nxgfi);
+            println!("  ok:{}\n", nxgfi);
+
+            let gwi = grapheme_width_at_idx(st, i);
+            print!("grapheme_width_from_idx");
+            assert_eq!(expected[i].7, gwi);
+            println!("  ok:{}", gwi);
+
+            let glen = grapheme_len(st, i);
+            print!("grapheme_len");
+            assert_eq!(expected[i].8, glen);
+            println!("  ok:{}", glen);
+
+            let nthg = nth_grapheme(st, i);
+            print!("nth_grapheme");
+            assert_eq!(expected[i].9, nthg);
+            println!("  ok:{}", nthg);
+
+            let ngw = nth_grapheme_width(st, i);
+            print!("nth grapheme_width");
+            assert_eq!(expected[i].10, ngw);
+            println!("  ok:{}", ngw);
+
+            let nthgi = nth_grapheme_idx(st, i);
+            print!("nth_grapheme_idx");
+            assert_eq!(expected[i].11, nthgi);
+            println!("  ok:{}", nthgi);
+
+            let numg = num_graphemes(st);
+            print!("num_graphemes");
+            assert_eq!(expected[i].12, numg);
+            println!("  ok:{}", numg);
+
+            let sw = string_width(st);
+            print!("string_width");
+            assert_eq!(expected[i].13, sw);
+            println!("  ok:{}", sw);
+        }
+    }
+
+    #[test]
+    fn test_grapheme_vectors() {
+        let test_cases: Vec<(String, Vec<TestData>)> = vec![
+            (
+                "".to_string(),
+                vec![
+                    (0, 0, 0, 0, "", "", "", 0, 0, "", 0, 0, 0, 0),
+                    (1, 0, 0, 0, "", "", "", 0, 0, "", 0, 0, 0, 0),
                 ],
             ),
             (
@@ -763,24 +3978,1956 @@ mod tests {
             ),
         ];
 
-        for (st, expected) in test_cases {
-            run_grapheme_test(&st, expected);
+        for (st, expected) in test_cases {
+            run_grapheme_test(&st, expected);
+        }
+    }
+
+    #[test]
+    fn test_num_graphemes() {
+        assert_eq!(num_graphemes(""), 0);
+        assert_eq!(num_graphemes("hello"), 5);
+        assert_eq!(num_graphemes("😊"), 1);
+        assert_eq!(num_graphemes("😊b"), 2);
+        assert_eq!(num_graphemes("a😊"), 2);
+        assert_eq!(num_graphemes("😊😊"), 2);
+        assert_eq!(num_graphemes("hello 😊 world"), 13);
+        assert_eq!(num_graphemes("é"), 1);
+        let complex_str = "áb̌c̃d̄";
+        assert_eq!(num_graphemes(complex_str), 4);
+        let flag_str = "🇫🇷"; // French flag
+        assert_eq!(num_graphemes(flag_str), 1);
+    }
+
+    #[cfg(feature = "ansi")]
+    #[test]
+    fn test_visible_width() {
+        assert_eq!(visible_width(""), 0);
+        assert_eq!(visible_width("hello"), 5);
+        assert_eq!(visible_width("\x1b[31m你好\x1b[0m"), 4);
+        assert_eq!(visible_width("\x1b[1;38;5;196mH\x1b[0m🧑"), 3);
+        assert_eq!(visible_width("\x1b]0;title\x07abc"), 3);
+        assert_eq!(visible_width("\x1b]8;;http://x\x1b\\link\x1b]8;;\x1b\\"), 4);
+        // Incomplete escapes at the end are ignored
+        assert_eq!(visible_width("你好\x1b[3"), 4);
+        assert_eq!(visible_width("你好\x1b"), 4);
+    }
+
+    #[cfg(feature = "ansi")]
+    #[test]
+    fn test_truncate_ansi_to_width() {
+        assert_eq!(truncate_ansi_to_width("", 3), "");
+        assert_eq!(truncate_ansi_to_width("hello", 3), "hel");
+        // Open color at the cut point gets a reset
+        assert_eq!(
+            truncate_ansi_to_width("\x1b[31mhello\x1b[0m", 3),
+            "\x1b[31mhel\x1b[0m"
+        );
+        assert_eq!(
+            truncate_ansi_to_width("\x1b[32m你好\x1b[0m", 3),
+            "\x1b[32m你\x1b[0m"
+        );
+        // Closed before the cut point, no reset
+        assert_eq!(
+            truncate_ansi_to_width("\x1b[31mhe\x1b[0mllo", 3),
+            "\x1b[31mhe\x1b[0ml"
+        );
+        assert_eq!(
+            truncate_ansi_to_width("\x1b[31;0mhello", 3),
+            "\x1b[31;0mhel"
+        );
+        assert_eq!(
+            truncate_ansi_to_width("\x1b[0;31mhello", 3),
+            "\x1b[0;31mhel\x1b[0m"
+        );
+        // A trailing 0 inside a 256 or truecolor code is a color, not a reset
+        assert_eq!(
+            truncate_ansi_to_width("\x1b[38;5;0mhello", 3),
+            "\x1b[38;5;0mhel\x1b[0m"
+        );
+        assert_eq!(
+            truncate_ansi_to_width("\x1b[38;2;255;0;0mhello", 3),
+            "\x1b[38;2;255;0;0mhel\x1b[0m"
+        );
+        assert_eq!(
+            truncate_ansi_to_width("\x1b[48;5;0;1mhello", 3),
+            "\x1b[48;5;0;1mhel\x1b[0m"
+        );
+        assert_eq!(
+            truncate_ansi_to_width("\x1b[38;5;0;0mhello", 3),
+            "\x1b[38;5;0;0mhel"
+        );
+        // Everything fits, nothing changes
+        assert_eq!(
+            truncate_ansi_to_width("\x1b[31mhello\x1b[0m", 5),
+            "\x1b[31mhello\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn test_is_malformed_emoji() {
+        assert!(is_malformed_emoji("\u{200D}", 0));
+        assert!(is_malformed_emoji("a\u{200D}", 0));
+        assert!(is_malformed_emoji("👨\u{200D}", 4));
+        assert!(is_malformed_emoji("\u{FE0F}", 0));
+        assert!(is_malformed_emoji("\u{1F3FB}", 0));
+        assert!(!is_malformed_emoji("", 0));
+        assert!(!is_malformed_emoji("a", 0));
+        assert!(!is_malformed_emoji("👍🏻", 0));
+        assert!(!is_malformed_emoji("❤\u{FE0F}", 0));
+        assert!(!is_malformed_emoji("👨\u{200D}👩", 0));
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        assert!((0..=st.len()).all(|i| !is_malformed_emoji(st, i)));
+    }
+
+    #[test]
+    fn test_required_width() {
+        assert_eq!(required_width(""), 0);
+        assert_eq!(required_width("abc"), 3);
+        assert_eq!(required_width("a\n\nabc\n"), 3);
+        assert_eq!(required_width("😊😊\nabc\n你好你好"), 8);
+        assert_eq!(required_width("H🧑🌾\r\nhi"), 5);
+        let st = "हिन्दी\nH🧑🌾e\n‘︀o‘︁réé";
+        assert_eq!(required_width(st), string_width("‘︀o‘︁réé"));
+    }
+
+    #[test]
+    fn test_grapheme_slice() {
+        let empty = GraphemeSlice::new("");
+        assert_eq!(empty.len(), 0);
+        assert!(empty.is_empty());
+        assert_eq!(empty.get(0), None);
+        assert_eq!(empty.iter().count(), 0);
+
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        let slice = GraphemeSlice::new(st);
+        assert_eq!(slice.len(), 12);
+        assert!(!slice.is_empty());
+        assert_eq!(&slice[0], "हि");
+        assert_eq!(&slice[1], "न्दी");
+        assert_eq!(&slice[2], "H");
+        assert_eq!(&slice[11], "e\u{301}");
+        assert_eq!(slice.get(1), Some("न्दी"));
+        assert_eq!(slice.get(12), None);
+        assert_eq!(
+            slice.iter().collect::<Vec<_>>(),
+            st.graphemes(true).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "grapheme index out of range")]
+    fn test_grapheme_slice_out_of_range() {
+        let slice = GraphemeSlice::new("ab");
+        let _ = &slice[2];
+    }
+
+    #[test]
+    fn test_longest_common_grapheme_substring() {
+        assert_eq!(longest_common_grapheme_substring("", ""), "");
+        assert_eq!(longest_common_grapheme_substring("abc", ""), "");
+        assert_eq!(longest_common_grapheme_substring("abc", "xyz"), "");
+        assert_eq!(longest_common_grapheme_substring("abc😊de", "x😊dy"), "😊d");
+        assert_eq!(longest_common_grapheme_substring("hello", "hello"), "hello");
+        assert_eq!(longest_common_grapheme_substring("abXcd", "cdYab"), "ab");
+        // The two é forms are different graphemes
+        assert_eq!(
+            longest_common_grapheme_substring("\u{e9}e\u{301}", "xe\u{301}y\u{e9}"),
+            "\u{e9}"
+        );
+        // e + U+301 never matches a bare e
+        assert_eq!(longest_common_grapheme_substring("e\u{301}x", "ey"), "");
+    }
+
+    #[test]
+    fn test_first_line_width() {
+        assert_eq!(first_line_width(""), 0);
+        assert_eq!(first_line_width("abc"), 3);
+        assert_eq!(first_line_width("abc\ndef"), 3);
+        assert_eq!(first_line_width("abc\r\ndef"), 3);
+        assert_eq!(first_line_width("你好\tdef"), 4);
+        assert_eq!(first_line_width("\nabc"), 0);
+        assert_eq!(first_line_width("हिन्दीH🧑🌾\ne‘︀o‘︁réé"), 10);
+    }
+
+    #[test]
+    fn test_grapheme_full_iter() {
+        assert_eq!(grapheme_full_iter("").count(), 0);
+
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        let infos: Vec<GraphemeInfo> = grapheme_full_iter(st).collect();
+        assert_eq!(infos.len(), 12);
+        assert_eq!(
+            infos[2],
+            GraphemeInfo {
+                byte_start: 18,
+                col_start: 5,
+                width: 1,
+                text: "H",
+                number: 2,
+            }
+        );
+        assert_eq!(
+            infos[3],
+            GraphemeInfo {
+                byte_start: 19,
+                col_start: 6,
+                width: 2,
+                text: "🧑",
+                number: 3,
+            }
+        );
+        let last = infos[11];
+        assert_eq!((last.byte_start, last.col_start, last.width), (44, 17, 1));
+        assert_eq!(last.col_start + last.width, string_width(st));
+    }
+
+    #[test]
+    fn test_clamp_grapheme_number() {
+        assert_eq!(clamp_grapheme_number("", 0), 0);
+        assert_eq!(clamp_grapheme_number("", 5), 0);
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        assert_eq!(clamp_grapheme_number(st, 99), 12);
+        assert_eq!(clamp_grapheme_number(st, 12), 12);
+        assert_eq!(clamp_grapheme_number(st, 3), 3);
+    }
+
+    #[test]
+    fn test_base_char_count() {
+        assert_eq!(base_char_count(""), 0);
+        assert_eq!(base_char_count("hello"), 5);
+        assert_eq!(base_char_count("\u{301}"), 0);
+        assert_eq!(base_char_count("\u{301}abc"), 3);
+        assert_eq!(base_char_count("\u{200D}a"), 1);
+        assert_eq!(base_char_count("e\u{301}"), 1);
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        assert_eq!(base_char_count(st), num_graphemes(st));
+    }
+
+    #[test]
+    fn test_byte_budget_chunks() {
+        assert_eq!(byte_budget_chunks("", 10), Vec::<&str>::new());
+        assert_eq!(byte_budget_chunks("abcde", 2), vec!["ab", "cd", "e"]);
+        assert_eq!(byte_budget_chunks("abc", 0), vec!["a", "b", "c"]);
+
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        let chunks = byte_budget_chunks(st, 10);
+        assert_eq!(chunks, vec!["हि", "न्दी", "H🧑🌾e", "‘︀o", "‘︁ré", "é"]);
+        assert_eq!(chunks.concat(), st);
+        for chunk in &chunks {
+            // Every chunk is whole graphemes, only an oversized single one may exceed
+            assert!(chunk.len() <= 10 || num_graphemes(chunk) == 1);
+            assert_eq!(chunk.graphemes(true).collect::<String>(), *chunk);
+        }
+        assert_eq!(byte_budget_chunks(st, 8)[1], "न्दी");
+    }
+
+    #[test]
+    fn test_nth_grapheme_from_end() {
+        assert_eq!(nth_grapheme_from_end("", 0), "");
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        assert_eq!(nth_grapheme_from_end(st, 0), "e\u{301}");
+        assert_eq!(nth_grapheme_from_end(st, 1), "\u{e9}");
+        assert_eq!(nth_grapheme_from_end(st, 9), "H");
+        assert_eq!(nth_grapheme_from_end(st, 11), "हि");
+        assert_eq!(nth_grapheme_from_end(st, 12), "");
+        for n in 0..12 {
+            assert_eq!(nth_grapheme_from_end(st, n), nth_grapheme(st, 11 - n));
+        }
+    }
+
+    #[test]
+    fn test_hconcat() {
+        assert_eq!(hconcat("", "", 2), "");
+        assert_eq!(hconcat("a", "b", 0), "ab");
+        assert_eq!(hconcat("你好\nab", "x\ny", 1), "你好 x\nab   y");
+        assert_eq!(hconcat("你好\nab", "x\ny\nz", 1), "你好 x\nab   y\n     z");
+        assert_eq!(hconcat("a\nbcd\nef", "1", 2), "a    1\nbcd  \nef   ");
+        let joined = hconcat("H🧑🌾\nhi", "|\n|", 1);
+        for line in joined.lines() {
+            assert_eq!(string_width(line), 7);
+        }
+    }
+
+    #[test]
+    fn test_has_wide_graphemes() {
+        assert!(!has_wide_graphemes(""));
+        assert!(!has_wide_graphemes("hello"));
+        assert!(!has_wide_graphemes("e\u{301}‘︀"));
+        assert!(has_wide_graphemes("हिन्दीH🧑🌾e‘︀o‘︁réé"));
+        assert!(has_wide_graphemes("abc你"));
+        assert!(has_wide_graphemes("😊"));
+    }
+
+    #[test]
+    fn test_grapheme_widths() {
+        assert_eq!(grapheme_widths(""), Vec::<u8>::new());
+        assert_eq!(grapheme_widths("ab你"), vec![1, 1, 2]);
+        assert_eq!(
+            grapheme_widths("हिन्दीH🧑🌾e‘︀o‘︁réé"),
+            vec![2, 3, 1, 2, 2, 1, 1, 1, 2, 1, 1, 1]
+        );
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        let total: usize = grapheme_widths(st).iter().map(|&w| w as usize).sum();
+        assert_eq!(total, string_width(st));
+    }
+
+    #[test]
+    fn test_first_grapheme_difference() {
+        assert_eq!(first_grapheme_difference("", ""), None);
+        assert_eq!(first_grapheme_difference("abé", "abé"), None);
+        assert_eq!(first_grapheme_difference("abé", "abX"), Some(2));
+        assert_eq!(first_grapheme_difference("ab", "abc"), Some(2));
+        assert_eq!(first_grapheme_difference("abc", ""), Some(0));
+        // Same leading bytes, but the grapheme differs
+        assert_eq!(first_grapheme_difference("xe", "xe\u{301}"), Some(1));
+        assert_eq!(
+            first_grapheme_difference("हिन्दीH🧑🌾e‘︀o‘︁réé", "हिन्दीH🧑"),
+            Some(4)
+        );
+    }
+
+    #[cfg(feature = "width-override")]
+    #[test]
+    fn test_register_width_override() {
+        // Tests run in parallel, so only touch a scalar no other test uses
+        let st = "x\u{2460}\u{FE0F}y";
+        assert_eq!(grapheme_width_at_idx(st, 1), 1);
+        register_width_override('\u{2460}', 2);
+        assert_eq!(grapheme_width_at_idx(st, 1), 2);
+        assert_eq!(string_width(st), 4);
+        assert_eq!(nth_grapheme_width(st, 1), 2);
+        assert_eq!(grapheme_widths(st), vec![1, 2, 1]);
+        register_width_override('\u{2460}', 3);
+        assert_eq!(string_width("\u{2460}"), 3);
+
+        // Per scalar widths and ambiguity use the overrides too
+        let st = "a\u{2461}";
+        assert_eq!(decompose_grapheme(st, 1), vec![('\u{2461}', 1)]);
+        assert!(has_ambiguous_width(st));
+        register_width_override('\u{2461}', 2);
+        assert_eq!(decompose_grapheme(st, 1), vec![('\u{2461}', 2)]);
+        assert!(!has_ambiguous_width(st));
+    }
+
+    #[test]
+    fn test_boundary_bitmask() {
+        assert_eq!(boundary_bitmask(""), 0);
+        assert_eq!(boundary_bitmask("a"), 0b1);
+        assert_eq!(boundary_bitmask("a\u{e9}"), 0b11);
+        assert_eq!(boundary_bitmask("ae\u{301}b"), 0b10011);
+        assert_eq!(boundary_bitmask("a\u{e9}b"), 0b1011);
+        assert_eq!(boundary_bitmask(&"a".repeat(64)), u64::MAX);
+        assert_eq!(boundary_bitmask(&"a".repeat(65)), 0);
+
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        let mask = boundary_bitmask(st);
+        for idx in 0..st.len() {
+            let is_boundary = grapheme_idx_at_idx(st, idx) == idx;
+            assert_eq!(mask & (1 << idx) != 0, is_boundary, "idx {}", idx);
+        }
+    }
+
+    #[test]
+    fn test_align_decimal() {
+        assert_eq!(align_decimal(&[], ' '), Vec::<String>::new());
+        assert_eq!(
+            align_decimal(&["1.5", "22.25", "3"], ' '),
+            vec![" 1.5 ", "22.25", " 3   "]
+        );
+        assert_eq!(align_decimal(&["1", "100"], '0'), vec!["001", "100"]);
+        assert_eq!(
+            align_decimal(&["一.5", "7.25"], '_'),
+            vec!["一.5_", "_7.25"]
+        );
+        // A wide fill never overshoots
+        assert_eq!(
+            align_decimal(&["1", "123"], '\u{3000}'),
+            vec!["\u{3000}1", "123"]
+        );
+    }
+
+    #[test]
+    fn test_line_widths() {
+        let empty = LineWidths::new("");
+        assert!(empty.is_empty());
+        assert_eq!(empty.total_max(), 0);
+
+        let mut widths = LineWidths::new("ab\n你好你好\nH🧑🌾");
+        assert_eq!(widths.len(), 3);
+        assert_eq!(widths.total_max(), 8);
+        assert_eq!(widths.width(2), 5);
+
+        // Shrinking the widest line finds the next widest
+        widths.on_edit(1, "hi");
+        assert_eq!(widths.width(1), 2);
+        assert_eq!(widths.total_max(), 5);
+
+        // Growing a line makes it the widest
+        widths.on_edit(0, "हिन्दीH🧑🌾e‘︀o‘︁réé");
+        assert_eq!(widths.total_max(), 18);
+
+        // Two lines tied for widest, shrinking one keeps the max
+        widths.on_edit(2, "हिन्दीH🧑🌾e‘︀o‘︁réé");
+        widths.on_edit(0, "");
+        assert_eq!(widths.total_max(), 18);
+        widths.on_edit(2, "a");
+        assert_eq!(widths.total_max(), 2);
+
+        // Editing past the end adds lines
+        widths.on_edit(5, "abc");
+        assert_eq!(widths.len(), 6);
+        assert_eq!(widths.width(4), 0);
+        assert_eq!(widths.total_max(), 3);
+    }
+
+    #[test]
+    fn test_grapheme_presentation() {
+        assert_eq!(grapheme_presentation("", 0), Presentation::Neutral);
+        assert_eq!(grapheme_presentation("a", 0), Presentation::Neutral);
+        assert_eq!(grapheme_presentation("你", 0), Presentation::Neutral);
+        assert_eq!(grapheme_presentation("😊", 0), Presentation::Emoji);
+        assert_eq!(grapheme_presentation("🇫🇷", 0), Presentation::Emoji);
+        assert_eq!(grapheme_presentation("☺", 0), Presentation::Text);
+        assert_eq!(grapheme_presentation("☺\u{FE0F}", 0), Presentation::Emoji);
+        assert_eq!(grapheme_presentation("😊\u{FE0E}", 0), Presentation::Text);
+        assert_eq!(
+            grapheme_presentation("1\u{FE0F}\u{20E3}", 0),
+            Presentation::Emoji
+        );
+
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        assert_eq!(grapheme_presentation(st, 19), Presentation::Emoji); // 🧑
+        assert_eq!(grapheme_presentation(st, 23), Presentation::Emoji); // 🌾
+        // U+FE00 and U+FE01 are not presentation selectors
+        assert_eq!(grapheme_presentation(st, 28), Presentation::Neutral); // ‘︀
+        assert_eq!(grapheme_presentation(st, 35), Presentation::Neutral); // ‘︁
+        // The same quote with VS15 / VS16
+        assert_eq!(grapheme_presentation("‘\u{FE0E}", 0), Presentation::Text);
+        assert_eq!(grapheme_presentation("‘\u{FE0F}", 0), Presentation::Emoji);
+    }
+
+    #[test]
+    fn test_rev_grapheme_col_iter() {
+        assert_eq!(rev_grapheme_col_iter("").count(), 0);
+        assert_eq!(
+            rev_grapheme_col_iter("ab你").collect::<Vec<_>>(),
+            vec![(2, "你"), (3, "b"), (4, "a")]
+        );
+
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        let items: Vec<(usize, &str)> = rev_grapheme_col_iter(st).collect();
+        assert_eq!(items.len(), 12);
+        assert_eq!(items[0], (1, "e\u{301}"));
+        assert_eq!(items[1], (2, "\u{e9}"));
+        assert_eq!(items[11], (18, "हि"));
+        // Matches the left to right columns
+        for (col, info) in items.iter().rev().zip(grapheme_full_iter(st)) {
+            assert_eq!(col.0, string_width(st) - info.col_start);
+        }
+    }
+
+    #[test]
+    fn test_overflow_width() {
+        assert_eq!(overflow_width("", 0), 0);
+        assert_eq!(overflow_width("abc", 0), 3);
+        assert_eq!(overflow_width("你好", 3), 1);
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        assert_eq!(overflow_width(st, 10), 8);
+        assert_eq!(overflow_width(st, 18), 0);
+        assert_eq!(overflow_width(st, 20), 0);
+    }
+
+    #[test]
+    fn test_strip_bom() {
+        assert_eq!(strip_bom(""), ("", false));
+        assert_eq!(strip_bom("\u{FEFF}"), ("", true));
+        assert_eq!(strip_bom("abc"), ("abc", false));
+        assert_eq!(strip_bom("\u{FEFF}abc"), ("abc", true));
+        // Only one leading BOM is stripped
+        assert_eq!(strip_bom("\u{FEFF}\u{FEFF}a"), ("\u{FEFF}a", true));
+        assert_eq!(strip_bom("a\u{FEFF}"), ("a\u{FEFF}", false));
+
+        let st = "\u{FEFF}हिन्दीH🧑🌾e‘︀o‘︁réé";
+        assert_eq!(strip_bom(st), ("हिन्दीH🧑🌾e‘︀o‘︁réé", true));
+        assert_eq!(string_width(st), string_width("हिन्दीH🧑🌾e‘︀o‘︁réé"));
+    }
+
+    #[cfg(feature = "skip-bom")]
+    #[test]
+    fn test_skip_bom() {
+        assert_eq!(num_graphemes("\u{FEFF}"), 0);
+        assert_eq!(num_graphemes("\u{FEFF}हिन्दीH🧑🌾e‘︀o‘︁réé"), 12);
+        assert_eq!(string_width("\u{FEFF}हिन्दीH🧑🌾e‘︀o‘︁réé"), 18);
+    }
+
+    #[test]
+    fn test_avg_grapheme_width() {
+        assert_eq!(avg_grapheme_width(""), 0.0);
+        assert_eq!(avg_grapheme_width("hello"), 1.0);
+        assert_eq!(avg_grapheme_width("你好世界"), 2.0);
+        assert_eq!(avg_grapheme_width("a你"), 1.5);
+        assert_eq!(avg_grapheme_width("हिन्दीH🧑🌾e‘︀o‘︁réé"), 1.5);
+    }
+
+    #[test]
+    fn test_is_inside_grapheme() {
+        assert!(!is_inside_grapheme("", 0));
+        assert!(!is_inside_grapheme("", 3));
+        assert!(!is_inside_grapheme("abc", 1));
+        assert!(is_inside_grapheme("e\u{301}", 1));
+        assert!(is_inside_grapheme("e\u{301}", 2));
+        assert!(!is_inside_grapheme("e\u{301}", 3));
+
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        assert!(is_inside_grapheme(st, 7));
+        assert!(!is_inside_grapheme(st, 6));
+        assert!(is_inside_grapheme(st, 17));
+        assert!(!is_inside_grapheme(st, 18));
+        assert!(!is_inside_grapheme(st, 47));
+        assert!(!is_inside_grapheme(st, 99));
+    }
+
+    #[test]
+    fn test_break_points_for_width() {
+        assert_eq!(break_points_for_width("", 5), Vec::<usize>::new());
+        assert_eq!(break_points_for_width("abc", 5), Vec::<usize>::new());
+        assert_eq!(break_points_for_width("abcdefg", 3), vec![3, 6]);
+        assert_eq!(break_points_for_width("a你好", 2), vec![1, 4]);
+        // Too wide for any row, the grapheme gets its own row
+        assert_eq!(break_points_for_width("a你b", 1), vec![1, 4]);
+
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        assert_eq!(break_points_for_width(st, 5), vec![18, 27, 41]);
+        assert_eq!(break_points_for_width(st, 18), Vec::<usize>::new());
+        for idx in break_points_for_width(st, 4) {
+            assert!(!is_inside_grapheme(st, idx));
+        }
+    }
+
+    #[test]
+    fn test_string_width_min_one() {
+        assert_eq!(string_width_min_one(""), 0);
+        assert_eq!(string_width_min_one("abc"), 3);
+        assert_eq!(string_width_min_one("\u{301}"), 1);
+        assert_eq!(string_width_min_one("\u{301}a你"), 4);
+        assert_eq!(string_width_min_one("a\u{200B}b"), 3);
+        assert_eq!(
+            string_width_min_one("हिन्दीH🧑🌾e‘︀o‘︁réé"),
+            string_width("हिन्दीH🧑🌾e‘︀o‘︁réé")
+        );
+    }
+
+    #[test]
+    fn test_nth_grapheme_to_utf16() {
+        assert_eq!(nth_grapheme_to_utf16("", 0), 0);
+        assert_eq!(nth_grapheme_to_utf16("", 3), 0);
+        assert_eq!(nth_grapheme_to_utf16("abc", 2), 2);
+        assert_eq!(nth_grapheme_to_utf16("😊b", 1), 2);
+
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        assert_eq!(nth_grapheme_to_utf16(st, 0), 0);
+        assert_eq!(nth_grapheme_to_utf16(st, 1), 2);
+        assert_eq!(nth_grapheme_to_utf16(st, 2), 6);
+        assert_eq!(nth_grapheme_to_utf16(st, 3), 7);
+        assert_eq!(nth_grapheme_to_utf16(st, 4), 9); // 🧑 is a surrogate pair
+        assert_eq!(nth_grapheme_to_utf16(st, 5), 11);
+        assert_eq!(nth_grapheme_to_utf16(st, 12), st.encode_utf16().count());
+        assert_eq!(nth_grapheme_to_utf16(st, 99), st.encode_utf16().count());
+    }
+
+    #[test]
+    fn test_is_monospace_safe() {
+        assert!(is_monospace_safe(""));
+        assert!(is_monospace_safe("abc"));
+        assert!(is_monospace_safe("你好 😊"));
+        assert!(is_monospace_safe("é"));
+        assert!(!is_monospace_safe("a\tb"));
+        assert!(!is_monospace_safe("a\nb"));
+        assert!(!is_monospace_safe("a\x1b[0m"));
+        assert!(!is_monospace_safe("a\u{7f}"));
+        assert!(!is_monospace_safe("a\u{200B}b"));
+        assert!(!is_monospace_safe("\u{301}a"));
+        assert!(!is_monospace_safe("\u{b1}")); // ± is ambiguous width
+    }
+
+    #[test]
+    fn test_grapheme_offsets_with_base() {
+        assert_eq!(grapheme_offsets_with_base("", 100), Vec::<usize>::new());
+        assert_eq!(grapheme_offsets_with_base("ab", 0), vec![0, 1]);
+
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        let offsets = grapheme_offsets_with_base(st, 100);
+        assert_eq!(offsets.len(), 12);
+        assert_eq!(offsets[0], 100);
+        assert_eq!(offsets[2], 118);
+        assert_eq!(offsets[11], 144);
+
+        // A sub-slice reports offsets into the whole string
+        let parent = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        assert_eq!(
+            grapheme_offsets_with_base(&parent[18..28], 18),
+            vec![18, 19, 23, 27]
+        );
+    }
+
+    #[test]
+    fn test_mask_to_width() {
+        assert_eq!(mask_to_width("", "*"), "");
+        assert_eq!(mask_to_width("你好", "*"), "****");
+        assert_eq!(mask_to_width("abc", "你"), "你 ");
+        assert_eq!(mask_to_width("ab", "\u{301}"), "  ");
+        assert_eq!(mask_to_width("abcd", "<>"), "<><>");
+        let masked = mask_to_width("हिन्दीH🧑🌾e‘︀o‘︁réé", "#");
+        assert_eq!(masked, "#".repeat(18));
+        assert_eq!(string_width(&mask_to_width("हिन्दीH🧑🌾e‘︀o‘︁réé", "😊")), 18);
+    }
+
+    #[test]
+    fn test_mask_graphemes() {
+        assert_eq!(mask_graphemes("", '*'), "");
+        assert_eq!(mask_graphemes("abc", '*'), "***");
+        assert_eq!(mask_graphemes("a🧑", '#'), "###");
+        assert_eq!(mask_graphemes("é", '*'), "*");
+        assert_eq!(mask_graphemes("a\u{200B}b", '*'), "**");
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        let masked = mask_graphemes(st, '*');
+        assert_eq!(masked, "*".repeat(18));
+        assert_eq!(string_width(&masked), string_width(st));
+    }
+
+    #[test]
+    fn test_grapheme_at_fraction() {
+        assert_eq!(grapheme_at_fraction("", 0.5), 0);
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        assert_eq!(grapheme_at_fraction(st, 0.5), nth_grapheme_idx(st, 6));
+        assert_eq!(grapheme_at_fraction(st, 0.5), 28);
+        assert_eq!(grapheme_at_fraction(st, 0.0), 0);
+        assert_eq!(grapheme_at_fraction(st, 0.1), 6); // 1.2 rounds to grapheme 1
+        assert_eq!(grapheme_at_fraction(st, 1.0), st.len());
+        assert_eq!(grapheme_at_fraction(st, 7.0), st.len());
+        assert_eq!(grapheme_at_fraction(st, -1.0), 0);
+        assert_eq!(grapheme_at_fraction(st, f64::NAN), 0);
+    }
+
+    #[test]
+    fn test_offset_table() {
+        let zero = ClusterOffsets {
+            byte: 0,
+            utf16: 0,
+            char: 0,
+        };
+        assert_eq!(offset_table(""), vec![zero]);
+
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        let table = offset_table(st);
+        assert_eq!(table.len(), 13);
+        // 🧑 is 4 bytes, a 2 unit surrogate pair and 1 char
+        assert_eq!(
+            table[3],
+            ClusterOffsets {
+                byte: 19,
+                utf16: 7,
+                char: 7,
+            }
+        );
+        assert_eq!(
+            table[4],
+            ClusterOffsets {
+                byte: 23,
+                utf16: 9,
+                char: 8,
+            }
+        );
+        // e + U+301 is 2 units and 2 chars
+        assert_eq!(table[11].byte, 44);
+        assert_eq!(table[12].utf16 - table[11].utf16, 2);
+        assert_eq!(table[12].char - table[11].char, 2);
+        assert_eq!(
+            *table.last().unwrap(),
+            ClusterOffsets {
+                byte: st.len(),
+                utf16: st.encode_utf16().count(),
+                char: st.chars().count(),
+            }
+        );
+        for (n, offsets) in table.iter().enumerate() {
+            assert_eq!(offsets.utf16, nth_grapheme_to_utf16(st, n));
+        }
+    }
+
+    #[test]
+    fn test_collapse_zero_width_runs() {
+        assert_eq!(collapse_zero_width_runs("", 1), "");
+        assert_eq!(collapse_zero_width_runs("abc", 0), "abc");
+        let flood = format!("a{}b", "\u{200B}".repeat(100));
+        assert_eq!(collapse_zero_width_runs(&flood, 2), "a\u{200B}\u{200B}b");
+        assert_eq!(collapse_zero_width_runs(&flood, 0), "ab");
+        assert_eq!(collapse_zero_width_runs(&flood, 200), flood);
+        // Each run is counted separately
+        assert_eq!(
+            collapse_zero_width_runs("\u{200B}\u{200B}x\u{200B}\u{FEFF}\u{200B}", 1),
+            "\u{200B}x\u{200B}"
+        );
+        // Combining marks belong to their grapheme and are untouched
+        assert_eq!(
+            collapse_zero_width_runs("हिन्दीH🧑🌾e‘︀o‘︁réé", 0),
+            "हिन्दीH🧑🌾e‘︀o‘︁réé"
+        );
+    }
+
+    #[test]
+    fn test_word_range_at_idx() {
+        assert_eq!(word_range_at_idx("", 0), (0, 0));
+        let st = "hello world";
+        assert_eq!(word_range_at_idx(st, 0), (0, 5));
+        assert_eq!(word_range_at_idx(st, 4), (0, 5));
+        assert_eq!(word_range_at_idx(st, 5), (5, 5));
+        assert_eq!(word_range_at_idx(st, 8), (6, 11));
+        assert_eq!(word_range_at_idx(st, 11), (11, 11));
+        assert_eq!(word_range_at_idx(st, 99), (11, 11));
+        assert_eq!(word_range_at_idx("a, b", 1), (1, 1));
+        // Inside a multi byte grapheme of a word
+        let st = "x réé y";
+        assert_eq!(word_range_at_idx(st, 6), (2, 8));
+        assert_eq!(&st[2..8], "réé");
+    }
+
+    #[test]
+    fn test_grapheme_word_flags() {
+        assert_eq!(grapheme_word_flags("").count(), 0);
+        let flags: Vec<(&str, bool)> = grapheme_word_flags("hello world").collect();
+        assert_eq!(flags.len(), 11);
+        assert_eq!(flags[0], ("h", true));
+        assert_eq!(flags[1], ("e", false));
+        assert_eq!(flags[5], (" ", false));
+        assert_eq!(flags[6], ("w", true));
+        let starts: Vec<&str> = grapheme_word_flags("¿qué? 2 éx, ok")
+            .filter(|(_, starts_word)| *starts_word)
+            .map(|(grapheme, _)| grapheme)
+            .collect();
+        assert_eq!(starts, vec!["q", "2", "é", "o"]);
+    }
+
+    #[test]
+    fn test_ascii_prefix_len() {
+        assert_eq!(ascii_prefix_len(""), 0);
+        assert_eq!(ascii_prefix_len("hello world"), 11);
+        assert_eq!(ascii_prefix_len("abc你好"), 3);
+        assert_eq!(ascii_prefix_len("你好"), 0);
+        assert_eq!(ascii_prefix_len("ab\tcd"), 2);
+        assert_eq!(ascii_prefix_len("ab\r\n"), 2);
+        assert_eq!(ascii_prefix_len("abc\u{301}d"), 2);
+        assert_eq!(ascii_prefix_len("हिन्दीH🧑🌾e‘︀o‘︁réé"), 0);
+        let st = "Hello, हिन्दीH🧑🌾e‘︀o‘︁réé";
+        let n = ascii_prefix_len(st);
+        assert_eq!(n, 7);
+        assert_eq!(string_width(&st[..n]), n);
+    }
+
+    #[test]
+    fn test_col_distance() {
+        assert_eq!(col_distance("", 0, 5), 0);
+        assert_eq!(col_distance("abc", 0, 2), 2);
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        assert_eq!(col_distance(st, 0, 18), 5);
+        assert_eq!(col_distance(st, 18, 0), -5);
+        assert_eq!(col_distance(st, 7, 18), 3); // 7 is inside न्दी
+        assert_eq!(col_distance(st, 18, 19), 1);
+        assert_eq!(col_distance(st, 19, 23), 2);
+        assert_eq!(col_distance(st, 0, 99), 18);
+        assert_eq!(col_distance(st, 10, 10), 0);
+    }
+
+    #[test]
+    fn test_visually_equal() {
+        assert!(visually_equal("", ""));
+        assert!(visually_equal("", "\u{200B}\u{FEFF}"));
+        assert!(visually_equal("ab", "a\u{200B}b"));
+        assert!(visually_equal("\u{FEFF}hello", "hello\u{200B}"));
+        assert!(visually_equal(
+            "हिन्दीH🧑🌾e‘︀o‘︁réé",
+            "हिन्दीH🧑🌾e‘︀o‘︁réé\u{200B}"
+        ));
+        assert!(!visually_equal("ab", "ba"));
+        assert!(!visually_equal("ab", "abc"));
+        assert!(!visually_equal("\u{e9}", "é"));
+    }
+
+    #[test]
+    fn test_write_ranges() {
+        let mut out = [(9, 9); 4];
+        assert_eq!(write_ranges("", &mut out), 0);
+        assert_eq!(out, [(9, 9); 4]);
+
+        // Everything fits, the rest of out is untouched
+        let st = "a你b";
+        assert_eq!(write_ranges(st, &mut out), 3);
+        assert_eq!(out, [(0, 1), (1, 4), (4, 5), (9, 9)]);
+
+        // Truncated, out is filled and the count says so
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        let mut out = [(0, 0); 3];
+        assert_eq!(write_ranges(st, &mut out), 3);
+        assert_eq!(out, [(0, 6), (6, 18), (18, 19)]);
+        assert!(num_graphemes(st) > out.len());
+
+        let mut all = vec![(0, 0); num_graphemes(st)];
+        assert_eq!(write_ranges(st, &mut all), 12);
+        assert_eq!(all[11], (44, 47));
+        assert_eq!(write_ranges(st, &mut []), 0);
+    }
+
+    #[test]
+    fn test_grapheme_sort_key() {
+        assert_eq!(grapheme_sort_key(""), Vec::<String>::new());
+        assert_eq!(grapheme_sort_key("aéb"), vec!["a", "é", "b"]);
+        assert_eq!(grapheme_sort_key("हिन्दीH🧑🌾e‘︀o‘︁réé").len(), 12);
+
+        // Bytes put the accented e between, clusters put it after
+        let mut words = vec!["e\u{301}", "e中", "ez"];
+        words.sort();
+        assert_eq!(words, vec!["ez", "e\u{301}", "e中"]);
+        words.sort_by_key(|w| grapheme_sort_key(w));
+        assert_eq!(words, vec!["ez", "e中", "e\u{301}"]);
+
+        // First differing cluster decides
+        assert!(grapheme_sort_key("abx") < grapheme_sort_key("acb"));
+        assert!(grapheme_sort_key("ab") < grapheme_sort_key("abc"));
+        assert_eq!(grapheme_sort_key("ab"), grapheme_sort_key("ab"));
+    }
+
+    #[test]
+    fn test_nearest_boundary_within() {
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        assert_eq!(nearest_boundary_within(st, 18, 0), Some(18));
+        assert_eq!(nearest_boundary_within(st, 18, 100), Some(18));
+        assert_eq!(nearest_boundary_within(st, 8, 4), Some(6));
+        assert_eq!(nearest_boundary_within(st, 16, 4), Some(18));
+        assert_eq!(nearest_boundary_within(st, 12, 3), None);
+        // Tie between 6 and 18 goes to the earlier one
+        assert_eq!(nearest_boundary_within(st, 12, 6), Some(6));
+        assert_eq!(nearest_boundary_within(st, 470, 0), Some(47));
+        assert_eq!(nearest_boundary_within("", 0, 0), Some(0));
+        assert_eq!(nearest_boundary_within("", 5, 0), Some(0));
+
+        // Deep combining run, the cap is respected
+        let st = format!("a{}", "\u{301}".repeat(50));
+        assert_eq!(st.len(), 101);
+        assert_eq!(nearest_boundary_within(&st, 51, 10), None);
+        assert_eq!(nearest_boundary_within(&st, 51, 50), Some(101));
+        assert_eq!(nearest_boundary_within(&st, 51, 51), Some(101));
+        assert_eq!(nearest_boundary_within(&st, 50, 1000), Some(0));
+    }
+
+    #[test]
+    fn test_grapheme_key() {
+        use std::collections::HashMap;
+        use std::hash::DefaultHasher;
+
+        let hash = |key: GraphemeKey| {
+            let mut hasher = DefaultHasher::new();
+            key.hash(&mut hasher);
+            hasher.finish()
+        };
+        let owned = String::from("हिन्दीH🧑🌾e‘︀o‘︁réé");
+        let a = GraphemeKey("हिन्दीH🧑🌾e‘︀o‘︁réé");
+        let b = GraphemeKey(&owned);
+        assert_eq!(a, b);
+        assert_eq!(hash(a), hash(b));
+        assert_eq!(hash(GraphemeKey("")), hash(GraphemeKey("")));
+
+        assert_ne!(GraphemeKey("e"), GraphemeKey("é"));
+        assert_ne!(GraphemeKey("ab"), GraphemeKey("a"));
+
+        let mut map = HashMap::new();
+        map.insert(GraphemeKey("é"), 1);
+        map.insert(GraphemeKey("e"), 2);
+        *map.entry(GraphemeKey(&owned)).or_insert(0) += 3;
+        assert_eq!(map[&GraphemeKey("é")], 1);
+        assert_eq!(map[&GraphemeKey("e")], 2);
+        assert_eq!(map[&a], 3);
+        assert_eq!(map.len(), 3);
+    }
+
+    #[cfg(feature = "normalize")]
+    #[test]
+    fn test_width_varies_by_normalization() {
+        assert!(!width_varies_by_normalization(""));
+        assert!(!width_varies_by_normalization("हिन्दीH🧑🌾e‘︀o‘︁réé"));
+        assert!(!width_varies_by_normalization("한국어 eé é"));
+    }
+
+    #[cfg(all(feature = "normalize", feature = "width-override"))]
+    #[test]
+    fn test_width_varies_by_normalization_override() {
+        // ǖ is only used here, the override can't leak into other tests
+        register_width_override('\u{1D6}', 2);
+        assert!(width_varies_by_normalization("\u{1D6}"));
+        assert!(width_varies_by_normalization("u\u{308}\u{304}"));
+        assert!(!width_varies_by_normalization("u\u{308}"));
+    }
+
+    #[test]
+    fn test_decompose_grapheme() {
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        assert_eq!(
+            decompose_grapheme(st, 35),
+            vec![('\u{2018}', 1), ('\u{FE01}', 0)]
+        );
+        assert_eq!(
+            decompose_grapheme(st, 38),
+            vec![('\u{2018}', 1), ('\u{FE01}', 0)]
+        );
+        assert_eq!(decompose_grapheme(st, 19), vec![('\u{1F9D1}', 2)]);
+        assert_eq!(decompose_grapheme(st, 23), vec![('\u{1F33E}', 2)]);
+        assert_eq!(decompose_grapheme(st, 44), vec![('e', 1), ('\u{301}', 0)]);
+        assert_eq!(decompose_grapheme(st, 470), vec![]);
+        assert_eq!(decompose_grapheme("", 0), vec![]);
+
+        // Woman technologist, ZWJ joined, the parts add up past the cluster
+        let st = "\u{1F469}\u{200D}\u{1F4BB}!";
+        assert_eq!(
+            decompose_grapheme(st, 0),
+            vec![('\u{1F469}', 2), ('\u{200D}', 0), ('\u{1F4BB}', 2)]
+        );
+        assert_eq!(nth_grapheme_width(st, 0), 2);
+    }
+
+    #[test]
+    fn test_caret_columns() {
+        assert_eq!(caret_columns(""), vec![0]);
+        assert_eq!(caret_columns("ab"), vec![0, 1, 2]);
+        assert_eq!(caret_columns("你a好"), vec![0, 2, 3, 5]);
+        assert_eq!(caret_columns("a\u{200B}b"), vec![0, 1, 2]);
+        assert_eq!(caret_columns("\u{200B}"), vec![0]);
+        assert_eq!(
+            caret_columns("हिन्दीH🧑🌾e‘︀o‘︁réé"),
+            vec![0, 2, 5, 6, 8, 10, 11, 12, 13, 15, 16, 17, 18]
+        );
+    }
+
+    #[test]
+    fn test_truncation_is_clean() {
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        assert!(truncation_is_clean(st, 18));
+        assert!(truncation_is_clean(st, 100));
+        assert!(truncation_is_clean(st, 0));
+        assert!(truncation_is_clean(st, 5));
+        assert!(truncation_is_clean(st, 6));
+        assert!(!truncation_is_clean(st, 1));
+        assert!(!truncation_is_clean(st, 7));
+        assert!(!truncation_is_clean(st, 3));
+        assert!(truncation_is_clean("", 0));
+
+        assert!(!truncation_is_clean("a😀b", 2));
+        assert!(truncation_is_clean("a😀b", 3));
+    }
+
+    #[test]
+    fn test_grapheme_byte_lengths() {
+        assert_eq!(grapheme_byte_lengths(""), Vec::<usize>::new());
+        assert_eq!(grapheme_byte_lengths("a你"), vec![1, 3]);
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        let lengths = grapheme_byte_lengths(st);
+        assert_eq!(lengths, vec![6, 12, 1, 4, 4, 1, 6, 1, 6, 1, 2, 3]);
+        assert_eq!(lengths.iter().sum::<usize>(), st.len());
+    }
+
+    #[test]
+    fn test_reflow() {
+        assert_eq!(reflow("", 10), "");
+        assert_eq!(reflow("one two three\nfour", 80), "one two three four");
+        assert_eq!(
+            reflow("one two\nthree four five\nsix", 9),
+            "one two\nthree\nfour five\nsix"
+        );
+
+        // Blank lines stay, trailing newline too
+        assert_eq!(reflow("a\nb\n\nc\nd\n", 10), "a b\n\nc d\n");
+        assert_eq!(reflow("a\n\n\nb", 10), "a\n\n\nb");
+        assert_eq!(reflow("a b\n  \nc", 10), "a b\n\nc");
+        assert_eq!(reflow("a\r\nb\r\n\r\nc", 10), "a b\n\nc");
+
+        // Widths are in columns, long words get their own line
+        assert_eq!(reflow("你好 你好 a", 6), "你好\n你好 a");
+        assert_eq!(reflow("你好 你好 a", 5), "你好\n你好\na");
+        assert_eq!(reflow("x abcdefgh y", 3), "x\nabcdefgh\ny");
+        assert_eq!(reflow("a b", 0), "a\nb");
+        assert_eq!(reflow("éé é", 3), "éé\né");
+    }
+
+    #[test]
+    fn test_changed_rows() {
+        assert_eq!(changed_rows("", "", 4), Vec::<usize>::new());
+        assert_eq!(
+            changed_rows("हिन्दीH🧑🌾e‘︀o‘︁réé", "हिन्दीH🧑🌾e‘︀o‘︁réé", 5),
+            Vec::<usize>::new()
+        );
+
+        // Insert on the second row, the overflow shifts into a new third row
+        assert_eq!(changed_rows("abcdefgh", "abcdeXfgh", 4), vec![1, 2]);
+        assert_eq!(changed_rows("abcdefgh", "abcdeXfgh", 8), vec![0, 1]);
+        assert_eq!(
+            changed_rows("abcd\nefgh\nijkl", "abcd\nefXh\nijkl", 4),
+            vec![1]
+        );
+        // The doc example, ijkl is pushed down a row by the wrap
+        assert_eq!(
+            changed_rows("abcd\nefgh\nijkl", "abcd\nefXgh\nijkl", 4),
+            vec![1, 2, 3]
+        );
+        assert_eq!(
+            changed_rows("abcd\nefgh\nijkl", "abcd\nefXgh\nijkl", 8),
+            vec![1]
+        );
+
+        // Rows only one side has count as changed
+        assert_eq!(changed_rows("ab", "ab\n", 4), vec![1]);
+        assert_eq!(changed_rows("ab\ncd\nef", "ab", 4), vec![1, 2]);
+        assert_eq!(changed_rows("ab\r\ncd", "ab\ncd", 4), Vec::<usize>::new());
+
+        // Wide graphemes wrap by column
+        assert_eq!(changed_rows("你好你好", "你好你们", 4), vec![1]);
+    }
+
+    #[test]
+    fn test_utf16_to_grapheme_number() {
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        assert_eq!(utf16_to_grapheme_number(st, 0), 0);
+        assert_eq!(utf16_to_grapheme_number(st, 1), 0);
+        assert_eq!(utf16_to_grapheme_number(st, 2), 1);
+        assert_eq!(utf16_to_grapheme_number(st, 6), 2);
+        assert_eq!(utf16_to_grapheme_number(st, 7), 3);
+        assert_eq!(utf16_to_grapheme_number(st, 8), 3);
+        assert_eq!(utf16_to_grapheme_number(st, 9), 4);
+        assert_eq!(utf16_to_grapheme_number(st, 10), 4);
+        assert_eq!(utf16_to_grapheme_number(st, 11), 5);
+        assert_eq!(utf16_to_grapheme_number(st, 20), 11);
+        assert_eq!(utf16_to_grapheme_number(st, 21), 12);
+        assert_eq!(utf16_to_grapheme_number(st, 999), 12);
+        assert_eq!(utf16_to_grapheme_number("", 3), 0);
+
+        // Round trips with nth_grapheme_to_utf16
+        for n in 0..=12 {
+            assert_eq!(
+                utf16_to_grapheme_number(st, nth_grapheme_to_utf16(st, n)),
+                n
+            );
         }
     }
 
     #[test]
-    fn test_num_graphemes() {
-        assert_eq!(num_graphemes(""), 0);
-        assert_eq!(num_graphemes("hello"), 5);
-        assert_eq!(num_graphemes("😊"), 1);
-        assert_eq!(num_graphemes("😊b"), 2);
-        assert_eq!(num_graphemes("a😊"), 2);
-        assert_eq!(num_graphemes("😊😊"), 2);
-        assert_eq!(num_graphemes("hello 😊 world"), 13);
-        assert_eq!(num_graphemes("é"), 1);
-        let complex_str = "áb̌c̃d̄";
-        assert_eq!(num_graphemes(complex_str), 4);
-        let flag_str = "🇫🇷"; // French flag
-        assert_eq!(num_graphemes(flag_str), 1);
+    fn test_repair_cursor() {
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        assert_eq!(repair_cursor(st, 0), 0);
+        assert_eq!(repair_cursor(st, 18), 18);
+        assert_eq!(repair_cursor(st, 10), 6);
+        assert_eq!(repair_cursor(st, 17), 6);
+        assert_eq!(repair_cursor(st, 20), 19);
+        assert_eq!(repair_cursor(st, 46), 44);
+        assert_eq!(repair_cursor(st, 47), 47);
+        assert_eq!(repair_cursor(st, 500), 47);
+        assert_eq!(repair_cursor("", 5), 0);
+
+        // The buffer shrank from under the cursor
+        let before = "abc你好";
+        let after = &before[..6];
+        assert_eq!(repair_cursor(after, 8), 6);
+        assert_eq!(repair_cursor(after, 5), 3);
+    }
+
+    #[test]
+    fn test_unique_graphemes() {
+        assert_eq!(unique_graphemes("", false), Vec::<&str>::new());
+        assert_eq!(unique_graphemes("", true), Vec::<&str>::new());
+        assert_eq!(unique_graphemes("banana", false), vec!["b", "a", "n"]);
+        assert_eq!(unique_graphemes("banana", true), vec!["a", "b", "n"]);
+
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        assert_eq!(unique_graphemes(st, false).len(), 12);
+        assert_eq!(
+            unique_graphemes(st, false),
+            st.graphemes(true).collect::<Vec<_>>()
+        );
+        assert_eq!(unique_graphemes(st, true).len(), 12);
+
+        // Whole clusters, e and é are different
+        assert_eq!(unique_graphemes("eéeé", false), vec!["e", "é"]);
+        assert_eq!(unique_graphemes("\u{e9}é", true).len(), 2);
+    }
+
+    #[test]
+    fn test_width_histogram() {
+        assert_eq!(width_histogram(""), BTreeMap::new());
+        assert_eq!(
+            width_histogram("हिन्दीH🧑🌾e‘︀o‘︁réé"),
+            BTreeMap::from([(1, 7), (2, 4), (3, 1)])
+        );
+        assert_eq!(
+            width_histogram("a\u{200B}你\u{200B}b"),
+            BTreeMap::from([(0, 2), (1, 2), (2, 1)])
+        );
+    }
+
+    #[test]
+    fn test_widest_line() {
+        assert_eq!(widest_line(""), (0, 0));
+        assert_eq!(widest_line("abc"), (0, 3));
+        assert_eq!(widest_line("ab\n你好你\nabcdef"), (1, 6));
+        assert_eq!(widest_line("a\nbcd\r\nefg\n"), (1, 3));
+        assert_eq!(widest_line("\n\nx"), (2, 1));
+        assert_eq!(widest_line("हिन्दीH🧑🌾e‘︀o‘︁réé\nहिन्दीH🧑🌾e‘︀o‘︁réé"), (0, 18));
+    }
+
+    #[test]
+    fn test_grapheme_similarity() {
+        assert_eq!(grapheme_similarity("", ""), 1.0);
+        assert_eq!(
+            grapheme_similarity("हिन्दीH🧑🌾e‘︀o‘︁réé", "हिन्दीH🧑🌾e‘︀o‘︁réé"),
+            1.0
+        );
+        assert_eq!(grapheme_similarity("abc", "xyz"), 0.0);
+        assert_eq!(grapheme_similarity("", "abc"), 0.0);
+        assert_eq!(grapheme_similarity("abcd", "abxd"), 0.75);
+        assert!((grapheme_similarity("kitten", "sitting") - 4.0 / 7.0).abs() < 1e-12);
+
+        // A whole cluster is one edit, न्दी is 4 scalars but 1 grapheme
+        assert_eq!(grapheme_similarity("हिन्दी", "हि"), 0.5);
+        assert_eq!(grapheme_similarity("aé", "ae"), 0.5);
+        assert_eq!(grapheme_similarity("你好", "好你"), 0.0);
+    }
+
+    #[test]
+    fn test_first_nonprinting_idx() {
+        assert_eq!(first_nonprinting_idx(""), None);
+        assert_eq!(first_nonprinting_idx("हिन्दीH🧑🌾e‘︀o‘︁réé"), None);
+        assert_eq!(first_nonprinting_idx("ab\x07cd"), Some(2));
+        assert_eq!(first_nonprinting_idx("你好\x07!\x07"), Some(6));
+        assert_eq!(first_nonprinting_idx("a\tb"), Some(1));
+        assert_eq!(first_nonprinting_idx("ab\r\n"), Some(2));
+        assert_eq!(first_nonprinting_idx("\x1b[31m"), Some(0));
+        assert_eq!(first_nonprinting_idx("a\u{200B}b"), Some(1));
+        assert_eq!(first_nonprinting_idx("\u{FEFF}a"), Some(0));
+
+        // Combining marks are fine, even a lone one at the start
+        assert_eq!(first_nonprinting_idx("aé\u{301}"), None);
+        assert_eq!(first_nonprinting_idx("\u{301}a"), None);
+    }
+
+    #[test]
+    fn test_grid_layout() {
+        assert_eq!(grid_layout(&[], 3, 2), "");
+        assert_eq!(grid_layout(&["a", "b", "c"], 3, 1), "a b c");
+        assert_eq!(grid_layout(&["a", "b", "c"], 0, 1), "a\nb\nc");
+
+        let items = ["a", "你好", "🧑b", "xyz", "é"];
+        let grid = grid_layout(&items, 2, 1);
+        assert_eq!(grid, "a    你好\n🧑b  xyz\né");
+        // The second column starts at the same visual column on every row
+        for line in grid.lines().take(2) {
+            let second = line.rfind(' ').unwrap() + 1;
+            assert_eq!(string_width(&line[..second]), 5);
+        }
+
+        assert_eq!(grid_layout(&["ab", "c", "d", "你"], 3, 2), "ab  c   d\n你");
+    }
+
+    #[test]
+    fn test_best_column_count() {
+        let items = ["abcde"; 10];
+        assert_eq!(best_column_count(&items, 40, 2), 6);
+        assert_eq!(best_column_count(&items, 39, 2), 5);
+        assert_eq!(best_column_count(&items, 40, 0), 8);
+        assert_eq!(best_column_count(&items[..3], 40, 2), 3);
+        assert_eq!(best_column_count(&items, 4, 2), 1);
+        assert_eq!(best_column_count(&[], 40, 2), 0);
+        assert_eq!(best_column_count(&[], 0, 0), 0);
+        assert_eq!(best_column_count(&["", ""], 0, 0), 2);
+
+        // Display width, 你好 is 4 columns not 6 bytes
+        assert_eq!(best_column_count(&["你好"; 5], 13, 1), 2);
+        assert_eq!(best_column_count(&["你好"; 5], 14, 1), 3);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_read_grapheme_chunks() {
+        let chunks = |input: &[u8], chunk_bytes| {
+            read_grapheme_chunks(input, chunk_bytes)
+                .map(|chunk| chunk.map_err(|e| e.kind()))
+                .collect::<Vec<_>>()
+        };
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        assert_eq!(chunks(b"", 4), vec![]);
+        assert_eq!(chunks(st.as_bytes(), 100), vec![Ok(st.to_string())]);
+
+        // Every buffer size, boundaries land inside clusters, output doesn't
+        for chunk_bytes in 0..50 {
+            let out: Vec<String> = chunks(st.as_bytes(), chunk_bytes)
+                .into_iter()
+                .map(Result::unwrap)
+                .collect();
+            assert_eq!(out.concat(), st);
+            let total: usize = out.iter().map(|chunk| num_graphemes(chunk)).sum();
+            assert_eq!(total, 12);
+        }
+        assert_eq!(
+            chunks(st.as_bytes(), 7),
+            ["हि", "न्दी", "H", "🧑🌾e", "‘︀", "o", "‘︁", "r\u{e9}é"]
+                .map(|chunk| Ok(chunk.to_string()))
+        );
+
+        // A trailing combining mark is held back until it can be joined
+        assert_eq!(
+            chunks("abé".as_bytes(), 2),
+            ["a", "b", "é"].map(|chunk| Ok(chunk.to_string()))
+        );
+        assert_eq!(
+            chunks("ééé".as_bytes(), 2),
+            ["é"; 3].map(|chunk| Ok(chunk.to_string()))
+        );
+
+        // Invalid UTF-8, the valid text first then the error
+        let out = chunks(b"ab\xffcd", 100);
+        assert_eq!(
+            out,
+            vec![Ok("ab".to_string()), Err(io::ErrorKind::InvalidData)]
+        );
+        let out = chunks(b"ab\xe4\xbd", 100);
+        assert_eq!(
+            out,
+            vec![Ok("ab".to_string()), Err(io::ErrorKind::InvalidData)]
+        );
+    }
+
+    #[test]
+    fn test_last_full_cluster_end() {
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        assert_eq!(last_full_cluster_end(st, 0), 0);
+        assert_eq!(last_full_cluster_end(st, 1), 0);
+        assert_eq!(last_full_cluster_end(st, 2), 6);
+        assert_eq!(last_full_cluster_end(st, 6), 19);
+        assert_eq!(last_full_cluster_end(st, 7), 19);
+        assert_eq!(last_full_cluster_end(st, 8), 23);
+        assert_eq!(last_full_cluster_end(st, 18), 47);
+        assert_eq!(last_full_cluster_end(st, 99), 47);
+        assert_eq!(last_full_cluster_end("", 5), 0);
+
+        // The straddling emoji is the faded tail
+        assert_eq!(last_full_cluster_end("ab😀c", 3), 2);
+        assert_eq!(last_full_cluster_end("ab\u{200B}c", 2), 5);
+    }
+
+    #[test]
+    fn test_interleave_graphemes() {
+        assert_eq!(interleave_graphemes("", ""), "");
+        assert_eq!(interleave_graphemes("abc", ""), "abc");
+        assert_eq!(interleave_graphemes("", "😊"), "😊");
+        assert_eq!(interleave_graphemes("abc", "😊😊"), "a😊b😊c");
+        assert_eq!(interleave_graphemes("ab", "xyz1"), "axbyz1");
+        assert_eq!(interleave_graphemes("éé", "हिन्दी"), "éहिéन्दी");
+    }
+
+    #[test]
+    fn test_caret_after_insert() {
+        assert_eq!(caret_after_insert("", 0, ""), 0);
+        assert_eq!(caret_after_insert("", 5, "ab"), 2);
+        assert_eq!(caret_after_insert("abc", 1, "xy"), 3);
+        assert_eq!(caret_after_insert("abc", 99, "x"), 4);
+
+        // Snapped to the grapheme start first
+        assert_eq!(caret_after_insert("हिन्दीH🧑🌾e‘︀o‘︁réé", 10, "x"), 7);
+
+        // Combining marks merge with the e before them
+        assert_eq!(caret_after_insert("eb", 1, "\u{301}"), 3);
+        assert_eq!(caret_after_insert("eb", 1, "\u{301}x"), 4);
+
+        // Merges with what follows move the caret past the merged grapheme
+        assert_eq!(caret_after_insert("🇸", 0, "🇺"), 8);
+        assert_eq!(caret_after_insert("a👩💻", 5, "\u{200D}"), 12);
+    }
+
+    #[test]
+    fn test_insertion_merges() {
+        assert!(insertion_merges("e", "\u{301}"));
+        assert!(insertion_merges("abe", "\u{301}x"));
+        assert!(!insertion_merges("e", "x"));
+        assert!(!insertion_merges("", "\u{301}"));
+        assert!(!insertion_merges("e", ""));
+        assert!(!insertion_merges("हिन्दीH🧑🌾e‘︀o‘︁réé", "a"));
+        assert!(insertion_merges("हिन्दीH🧑🌾e‘︀o‘︁réé", "\u{FE0F}"));
+
+        // Needs context from before to decide
+        assert!(insertion_merges("👩\u{200D}", "💻"));
+        assert!(insertion_merges("x🇺", "🇸"));
+        assert!(!insertion_merges("🇺🇸", "🇫"));
+        assert!(insertion_merges("🇺🇸🇫", "🇷"));
+        assert!(insertion_merges("\r", "\n"));
+    }
+
+    #[test]
+    fn test_truncate_reserving() {
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        let prefix = truncate_reserving(st, 18, 3);
+        assert_eq!(prefix, &st[..41]);
+        assert_eq!(string_width(prefix), 15);
+        assert_eq!(truncate_reserving(st, 18, 0), st);
+        assert_eq!(truncate_reserving(st, 100, 3), st);
+        assert_eq!(truncate_reserving(st, 10, 3), &st[..19]);
+        assert_eq!(truncate_reserving(st, 3, 3), "");
+        assert_eq!(truncate_reserving(st, 3, 30), "");
+        assert_eq!(truncate_reserving("\u{200B}ab", 2, 2), "");
+        assert_eq!(truncate_reserving("", 10, 3), "");
+    }
+
+    #[test]
+    fn test_byte_range_to_grapheme_range() {
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        assert_eq!(byte_range_to_grapheme_range(st, 6, 18), (1, 2));
+        assert_eq!(byte_range_to_grapheme_range(st, 0, 47), (0, 12));
+        assert_eq!(byte_range_to_grapheme_range(st, 7, 20), (1, 4));
+        assert_eq!(byte_range_to_grapheme_range(st, 7, 8), (1, 2));
+        assert_eq!(byte_range_to_grapheme_range(st, 18, 18), (2, 2));
+        assert_eq!(byte_range_to_grapheme_range(st, 45, 99), (11, 12));
+        assert_eq!(byte_range_to_grapheme_range(st, 99, 120), (12, 12));
+        assert_eq!(byte_range_to_grapheme_range(st, 19, 6), (3, 3));
+        assert_eq!(byte_range_to_grapheme_range("", 0, 5), (0, 0));
+    }
+
+    #[test]
+    fn test_count_astral_graphemes() {
+        assert_eq!(count_astral_graphemes(""), 0);
+        assert_eq!(count_astral_graphemes("abc你好é"), 0);
+        assert_eq!(count_astral_graphemes("हिन्दीH🧑🌾e‘︀o‘︁réé"), 2);
+        // One grapheme with two astral scalars still counts once
+        assert_eq!(count_astral_graphemes("👩\u{200D}💻a😀"), 2);
+        assert_eq!(count_astral_graphemes("𠀀"), 1);
+    }
+
+    #[test]
+    fn test_grapheme_at_row_col() {
+        assert_eq!(grapheme_at_row_col("aaaaaa", 3, 1, 0), "a");
+        let st = "abcdef";
+        assert_eq!(grapheme_at_row_col(st, 3, 0, 0), "a");
+        assert_eq!(grapheme_at_row_col(st, 3, 1, 0), "d");
+        assert_eq!(grapheme_at_row_col(st, 3, 1, 2), "f");
+        assert_eq!(grapheme_at_row_col(st, 3, 1, 3), "");
+        assert_eq!(grapheme_at_row_col(st, 3, 2, 0), "");
+        assert_eq!(grapheme_at_row_col("", 3, 0, 0), "");
+
+        // Wide graphemes wrap whole, both halves hit
+        let st = "a你好";
+        assert_eq!(grapheme_at_row_col(st, 3, 0, 1), "你");
+        assert_eq!(grapheme_at_row_col(st, 3, 0, 2), "你");
+        assert_eq!(grapheme_at_row_col(st, 3, 1, 0), "好");
+        assert_eq!(grapheme_at_row_col(st, 3, 1, 1), "好");
+
+        // Forced breaks start a new row
+        assert_eq!(grapheme_at_row_col("ab\ncd", 10, 1, 1), "d");
+        assert_eq!(grapheme_at_row_col("ab\ncd", 10, 0, 2), "");
+
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        assert_eq!(grapheme_at_row_col(st, 5, 1, 2), "\u{1F9D1}");
+        assert_eq!(grapheme_at_row_col(st, 5, 3, 2), "é");
+    }
+
+    #[test]
+    fn test_row_of_idx() {
+        let st = "abcdefghij";
+        assert_eq!(row_of_idx(st, 4, 0), 0);
+        assert_eq!(row_of_idx(st, 4, 3), 0);
+        assert_eq!(row_of_idx(st, 4, 4), 1);
+        assert_eq!(row_of_idx(st, 4, 9), 2);
+        assert_eq!(row_of_idx(st, 4, 10), 2);
+        assert_eq!(row_of_idx(st, 4, 99), 2);
+        assert_eq!(row_of_idx("", 4, 0), 0);
+
+        // Forced breaks, the \n is on the row it ends
+        let st = "ab\n\nabcdef";
+        assert_eq!(row_of_idx(st, 4, 2), 0);
+        assert_eq!(row_of_idx(st, 4, 3), 1);
+        assert_eq!(row_of_idx(st, 4, 4), 2);
+        assert_eq!(row_of_idx(st, 4, 9), 3);
+
+        // Snapped to the grapheme, the README rows start at 0, 18, 27 and 41
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        assert_eq!(row_of_idx(st, 5, 17), 0);
+        assert_eq!(row_of_idx(st, 5, 25), 1);
+        assert_eq!(row_of_idx(st, 5, 41), 3);
+    }
+
+    #[test]
+    fn test_split_balanced_by_width() {
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        assert_eq!(
+            split_balanced_by_width(st, 3),
+            vec![&st[..19], &st[19..34], &st[34..]]
+        );
+        assert_eq!(split_balanced_by_width(st, 1), vec![st]);
+        assert_eq!(split_balanced_by_width(st, 0), vec![st]);
+        let widths: Vec<usize> = split_balanced_by_width(st, 2)
+            .iter()
+            .map(|piece| string_width(piece))
+            .collect();
+        assert_eq!(widths, vec![8, 10]);
+
+        // Wide graphemes can't be split, the pieces are as close as it gets
+        assert_eq!(split_balanced_by_width("你好a", 2), vec!["你", "好a"]);
+        assert_eq!(
+            split_balanced_by_width("abcdefg", 3),
+            vec!["ab", "cde", "fg"]
+        );
+
+        // Too few graphemes, empty pieces fill out the count
+        assert_eq!(split_balanced_by_width("ab", 4), vec!["", "a", "", "b"]);
+        assert_eq!(split_balanced_by_width("", 2), vec!["", ""]);
+    }
+
+    #[test]
+    fn test_indented_width() {
+        assert_eq!(indented_width("", 0), 0);
+        assert_eq!(indented_width("", 6), 6);
+        assert_eq!(indented_width("हिन्दीH🧑🌾e‘︀o‘︁réé", 2), 20);
+
+        // Tabs expand to the next stop from the indent column
+        assert_eq!(indented_width("\tab", 0), 10);
+        assert_eq!(indented_width("\tab", 4), 10);
+        assert_eq!(indented_width("\tab", 8), 18);
+        assert_eq!(indented_width("a\tb", 4), 9);
+        assert_eq!(indented_width("\t\t", 1), 16);
+        assert_eq!(indented_width("你\t", 5), 8);
+    }
+
+    #[test]
+    fn test_has_ambiguous_width() {
+        assert!(!has_ambiguous_width(""));
+        assert!(!has_ambiguous_width("plain ascii 123"));
+        assert!(!has_ambiguous_width("你好"));
+        assert!(has_ambiguous_width("\u{2018}"));
+        assert!(!has_ambiguous_width("caf\u{e9}"));
+        assert!(has_ambiguous_width("\u{b1}1"));
+        assert!(has_ambiguous_width("\u{2460}"));
+        // The README quotes carry a variation selector, which settles the width
+        assert!(!has_ambiguous_width("हिन्दीH🧑🌾e‘︀o‘︁réé"));
+        assert!(has_ambiguous_width("\u{2018}o"));
+    }
+
+    #[test]
+    fn test_located_graphemes() {
+        assert_eq!(located_graphemes("").count(), 0);
+        assert_eq!(
+            located_graphemes("a你\nb好c").collect::<Vec<_>>(),
+            vec![
+                (0, 0, "a"),
+                (0, 1, "你"),
+                (0, 3, "\n"),
+                (1, 0, "b"),
+                (1, 1, "好"),
+                (1, 3, "c")
+            ]
+        );
+        assert_eq!(
+            located_graphemes("x\r\n\né").collect::<Vec<_>>(),
+            vec![(0, 0, "x"), (0, 1, "\r\n"), (1, 0, "\n"), (2, 0, "é")]
+        );
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé\nहिन्दीH🧑🌾e‘︀o‘︁réé";
+        let second: Vec<_> = located_graphemes(st)
+            .filter(|(line, _, _)| *line == 1)
+            .collect();
+        assert_eq!(second.len(), 12);
+        assert_eq!(second[2], (1, 5, "H"));
+    }
+
+    #[test]
+    fn test_idx_of_line_col() {
+        let st = "a你\nb好c";
+        assert_eq!(idx_of_line_col(st, 0, 0), 0);
+        assert_eq!(idx_of_line_col(st, 0, 1), 1);
+        assert_eq!(idx_of_line_col(st, 0, 2), 1);
+        assert_eq!(idx_of_line_col(st, 0, 3), 4);
+        assert_eq!(idx_of_line_col(st, 0, 99), 4);
+        assert_eq!(idx_of_line_col(st, 1, 0), 5);
+        assert_eq!(idx_of_line_col(st, 1, 2), 6);
+        assert_eq!(idx_of_line_col(st, 1, 3), 9);
+        assert_eq!(idx_of_line_col(st, 1, 4), 10);
+        assert_eq!(idx_of_line_col(st, 5, 0), 10);
+        assert_eq!(idx_of_line_col("", 0, 0), 0);
+
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé\r\nहिन्दीH🧑🌾e‘︀o‘︁réé";
+        assert_eq!(idx_of_line_col(st, 1, 0), 49);
+        assert_eq!(idx_of_line_col(st, 0, 50), 47);
+        assert_eq!(idx_of_line_col(st, 1, 5), 49 + 18);
+
+        // Round trips with located_graphemes
+        for ((idx, _), (line, col, _)) in st.grapheme_indices(true).zip(located_graphemes(st)) {
+            assert_eq!(idx_of_line_col(st, line, col), idx);
+        }
+    }
+
+    #[test]
+    fn test_width_rle() {
+        assert_eq!(width_rle(""), vec![]);
+        assert_eq!(width_rle("aaa你你"), vec![(1, 3), (2, 2)]);
+        assert_eq!(width_rle(&"x".repeat(1000)), vec![(1, 1000)]);
+        assert_eq!(width_rle("a\u{200B}b"), vec![(1, 1), (0, 1), (1, 1)]);
+        assert_eq!(
+            width_rle("हिन्दीH🧑🌾e‘︀o‘︁réé"),
+            vec![(2, 1), (3, 1), (1, 1), (2, 2), (1, 3), (2, 1), (1, 3)]
+        );
+    }
+
+    #[test]
+    fn test_insert_keeps_monospace() {
+        assert!(insert_keeps_monospace("", 0, ""));
+        assert!(insert_keeps_monospace("cell", 2, "xy"));
+        assert!(insert_keeps_monospace("cell", 99, "你好"));
+        assert!(!insert_keeps_monospace("cell", 2, "\t"));
+        assert!(!insert_keeps_monospace("cell", 2, "a\u{200B}"));
+        assert!(!insert_keeps_monospace("cell", 4, "\u{2018}"));
+
+        // Existing problems outside the insert don't count
+        assert!(insert_keeps_monospace("a\tb", 0, "x"));
+        assert!(!is_monospace_safe("a\tb"));
+
+        // A combining mark merges with the c before it, that's still fine
+        assert!(insert_keeps_monospace("cell", 1, "\u{301}"));
+        // The merged grapheme is checked, a variation selector settles the
+        // ambiguous ‘ while a zero width space after it doesn't merge
+        assert!(insert_keeps_monospace("\u{2018}", 3, "\u{FE00}"));
+        assert!(!insert_keeps_monospace("\u{2018}", 3, "\u{200B}"));
+        assert!(!insert_keeps_monospace("ab", 1, "\u{2018}"));
+    }
+
+    #[test]
+    fn test_boundary_pairs() {
+        assert_eq!(boundary_pairs("").count(), 0);
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        let pairs: Vec<_> = boundary_pairs(st).collect();
+        assert_eq!(pairs.len(), 12);
+        assert_eq!(pairs[0], (0, 6));
+        assert_eq!(pairs[1], (6, 18));
+        assert_eq!(pairs[11], (44, 47));
+        assert!(pairs.windows(2).all(|w| w[0].1 == w[1].0));
+        assert_eq!(
+            boundary_pairs("a你").collect::<Vec<_>>(),
+            vec![(0, 1), (1, 4)]
+        );
+    }
+
+    #[test]
+    fn test_width_after_removal() {
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        assert_eq!(width_after_removal(st, 6, 18), 15);
+        assert_eq!(width_after_removal(st, 7, 18), 15);
+        assert_eq!(width_after_removal(st, 6, 20), 14);
+        assert_eq!(width_after_removal(st, 0, 47), 0);
+        assert_eq!(width_after_removal(st, 0, 999), 0);
+        assert_eq!(width_after_removal(st, 18, 18), 18);
+        assert_eq!(width_after_removal(st, 19, 6), 18);
+        assert_eq!(width_after_removal("", 0, 5), 0);
+        assert_eq!(width_after_removal("a你b", 1, 4), 2);
+    }
+
+    #[test]
+    fn test_grapheme_diff() {
+        use DiffOp::*;
+
+        assert_eq!(grapheme_diff("", ""), vec![]);
+        assert_eq!(
+            grapheme_diff("हिन्दीH🧑🌾e‘︀o‘︁réé", "हिन्दीH🧑🌾e‘︀o‘︁réé"),
+            vec![Equal(12)]
+        );
+        assert_eq!(grapheme_diff("", "a你"), vec![Insert("a你".to_string())]);
+        assert_eq!(grapheme_diff("a你", ""), vec![Delete(2)]);
+        assert_eq!(
+            grapheme_diff("abé", "abXé"),
+            vec![Equal(2), Insert("X".to_string()), Equal(1)]
+        );
+
+        // Replacing the accented e is one grapheme, not a byte inside it
+        assert_eq!(
+            grapheme_diff("abéc", "abec"),
+            vec![Equal(2), Delete(1), Insert("e".to_string()), Equal(1)]
+        );
+        assert_eq!(
+            grapheme_diff("kitten", "sitting"),
+            vec![
+                Delete(1),
+                Insert("s".to_string()),
+                Equal(3),
+                Delete(1),
+                Insert("i".to_string()),
+                Equal(1),
+                Insert("g".to_string())
+            ]
+        );
+
+        // Applying the ops to old rebuilds new
+        let (old, new) = ("हिन्दीH🧑🌾e‘︀o‘︁réé", "हि🌾e‘︁xé");
+        let mut graphemes = old.graphemes(true);
+        let mut rebuilt = String::new();
+        for op in grapheme_diff(old, new) {
+            match op {
+                Equal(n) => rebuilt.extend(graphemes.by_ref().take(n)),
+                Insert(text) => rebuilt.push_str(&text),
+                Delete(n) => graphemes.by_ref().take(n).for_each(drop),
+            }
+        }
+        assert_eq!(rebuilt, new);
+    }
+
+    #[test]
+    fn test_grapheme_after_match() {
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        assert_eq!(grapheme_after_match(st, "H"), Some("\u{1F9D1}"));
+        assert_eq!(grapheme_after_match(st, "H\u{1F9D1}"), Some("\u{1F33E}"));
+        assert_eq!(grapheme_after_match(st, "हि"), Some("न्दी"));
+        assert_eq!(grapheme_after_match(st, "r\u{e9}"), Some("é"));
+        assert_eq!(grapheme_after_match(st, "é"), None);
+        assert_eq!(grapheme_after_match(st, "zz"), None);
+        assert_eq!(grapheme_after_match(st, ""), None);
+        assert_eq!(grapheme_after_match("", "a"), None);
+
+        // Not aligned, the e of an accented e doesn't match
+        assert_eq!(grapheme_after_match("éxex", "e"), Some("x"));
+        assert_eq!(grapheme_after_match("aé", "a"), Some("é"));
+        assert_eq!(grapheme_after_match("abé", "abe"), None);
+        // Inside a grapheme doesn't count either
+        assert_eq!(grapheme_after_match("e\u{301}\u{302}x", "\u{302}"), None);
+    }
+
+    #[test]
+    fn test_graphemes_with_override() {
+        let never = |_: &str, _: &str| false;
+        assert_eq!(graphemes_with_override("", never), Vec::<&str>::new());
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        assert_eq!(
+            graphemes_with_override(st, never),
+            st.graphemes(true).collect::<Vec<_>>()
+        );
+        assert_eq!(graphemes_with_override(st, |_, _| true), vec![st]);
+
+        // The farmer halves glued back together
+        let glued = graphemes_with_override(st, |left, right| {
+            left == "\u{1F9D1}" && right == "\u{1F33E}"
+        });
+        assert_eq!(glued.len(), 11);
+        assert_eq!(glued[3], "\u{1F9D1}\u{1F33E}");
+        assert_eq!(glued[4], "e");
+
+        // left is the glued run so far, a [tag] stays whole
+        let tags = graphemes_with_override("a[b你]c", |left, _| {
+            left.starts_with('[') && !left.ends_with(']')
+        });
+        assert_eq!(tags, vec!["a", "[b你]", "c"]);
+    }
+
+    #[test]
+    fn test_grapheme_spans() {
+        assert_eq!(grapheme_spans("").count(), 0);
+        assert_eq!(
+            grapheme_spans("a你é").collect::<Vec<_>>(),
+            vec![(0, "a", 1), (1, "你", 2), (4, "é", 1)]
+        );
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        let spans: Vec<_> = grapheme_spans(st).collect();
+        assert_eq!(spans.len(), 12);
+        assert_eq!(spans[4], (23, "\u{1F33E}", 2));
+        assert_eq!(spans[11], (44, "é", 1));
+        assert_eq!(
+            spans.iter().map(|(_, _, w)| w).sum::<usize>(),
+            string_width(st)
+        );
+    }
+
+    #[test]
+    fn test_proportional_cells() {
+        assert_eq!(proportional_cells(&[], 10), Vec::<usize>::new());
+        assert_eq!(proportional_cells(&["a", "你", "abc"], 12), vec![2, 4, 6]);
+        assert_eq!(proportional_cells(&["a", "b", "c"], 10), vec![4, 3, 3]);
+        assert_eq!(proportional_cells(&["a", "bb", "ccc"], 10), vec![2, 3, 5]);
+        assert_eq!(proportional_cells(&["a", "bb"], 0), vec![0, 0]);
+        assert_eq!(proportional_cells(&["", "abc"], 5), vec![0, 5]);
+        assert_eq!(proportional_cells(&["", "\u{200B}"], 5), vec![3, 2]);
+        assert_eq!(
+            proportional_cells(&["हिन्दीH🧑🌾e‘︀o‘︁réé", "ab"], 10),
+            vec![9, 1]
+        );
+
+        for total in 0..40 {
+            let cells = proportional_cells(&["x", "你好", "🧑🌾e", "é"], total);
+            assert_eq!(cells.iter().sum::<usize>(), total);
+        }
+    }
+
+    #[test]
+    fn test_nearest_boundary_in() {
+        assert_eq!(nearest_boundary_in(&[], 5), 0);
+        for st in ["", "a", "हिन्दीH🧑🌾e‘︀o‘︁réé", "a你é\u{200B}b"] {
+            let mut offsets = grapheme_offsets_with_base(st, 0);
+            offsets.push(st.len());
+            for idx in 0..st.len() + 5 {
+                assert_eq!(
+                    nearest_boundary_in(&offsets, idx),
+                    grapheme_idx_at_idx(st, idx)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_truncate_to_width() {
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        assert_eq!(truncate_to_width(st, 0), "");
+        assert_eq!(truncate_to_width(st, 1), "");
+        assert_eq!(truncate_to_width(st, 4), "हि");
+        assert_eq!(truncate_to_width(st, 5), &st[..18]);
+        assert_eq!(truncate_to_width(st, 7), &st[..19]);
+        assert_eq!(string_width(truncate_to_width(st, 7)), 6);
+        assert_eq!(truncate_to_width(st, 10), &st[..27]);
+        assert_eq!(truncate_to_width(st, 18), st);
+        assert_eq!(truncate_to_width(st, 99), st);
+        assert_eq!(truncate_to_width("", 3), "");
+        for max_width in 0..20 {
+            assert!(string_width(truncate_to_width(st, max_width)) <= max_width);
+        }
+    }
+
+    #[test]
+    fn test_pad_to_width() {
+        assert_eq!(pad_to_width("", 3, ' '), "   ");
+        assert_eq!(pad_to_width("ab", 5, '-'), "ab---");
+        assert_eq!(pad_to_width("你好", 6, '.'), "你好..");
+        assert_eq!(pad_to_width("你好", 4, '.'), "你好");
+        assert_eq!(pad_to_width("你好", 2, '.'), "你好");
+        assert_eq!(
+            pad_to_width("हिन्दीH🧑🌾e‘︀o‘︁réé", 20, ' '),
+            "हिन्दीH🧑🌾e‘︀o‘︁réé  "
+        );
+
+        // A wide fill never overshoots
+        assert_eq!(pad_to_width("a", 6, '\u{3000}'), "a\u{3000}\u{3000}");
+        assert_eq!(string_width(&pad_to_width("a", 6, '\u{3000}')), 5);
+        assert_eq!(pad_to_width("a", 5, '\u{3000}'), "a\u{3000}\u{3000}");
+    }
+
+    #[test]
+    fn test_string_width_scaled() {
+        assert_eq!(string_width_scaled("", |_| 2.0), 0.0);
+        for st in ["abc", "你好", "हिन्दीH🧑🌾e‘︀o‘︁réé", "a\u{200B}b"]
+        {
+            assert_eq!(string_width_scaled(st, |_| 1.0), string_width(st) as f64);
+            assert_eq!(
+                string_width_scaled(st, |_| 0.5),
+                string_width(st) as f64 / 2.0
+            );
+        }
+        let emoji_and_a_half = |g: &str| if has_wide_graphemes(g) { 1.5 } else { 1.0 };
+        assert_eq!(string_width_scaled("a你😀", emoji_and_a_half), 7.0);
+    }
+
+    #[test]
+    fn test_graphemes_treating_nul_as() {
+        assert_eq!(graphemes_treating_nul_as("", "?"), Vec::<String>::new());
+        assert_eq!(graphemes_treating_nul_as("ab", "?"), vec!["a", "b"]);
+        assert_eq!(
+            graphemes_treating_nul_as("a\0b", "\u{2400}"),
+            vec!["a", "\u{2400}", "b"]
+        );
+        assert_eq!(graphemes_treating_nul_as("\0\0", "\\0"), vec!["\\0", "\\0"]);
+        // A NUL never joins a combining mark, the mark stays on its own
+        assert_eq!(
+            graphemes_treating_nul_as("\0\u{301}é", "?"),
+            vec!["?", "\u{301}", "é"]
+        );
+    }
+
+    #[test]
+    fn test_column_to_idx() {
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        assert_eq!(column_to_idx(st, 0), 0);
+        assert_eq!(column_to_idx(st, 1), 0);
+        assert_eq!(column_to_idx(st, 2), 6);
+        assert_eq!(column_to_idx(st, 3), 6);
+        assert_eq!(column_to_idx(st, 4), 6);
+        assert_eq!(column_to_idx(st, 5), 18);
+        assert_eq!(column_to_idx(st, 6), 19);
+        assert_eq!(column_to_idx(st, 7), 19);
+        assert_eq!(column_to_idx(st, 17), 44);
+        assert_eq!(column_to_idx(st, 18), 47);
+        assert_eq!(column_to_idx(st, 99), 47);
+        assert_eq!(column_to_idx("", 0), 0);
+        // Zero width graphemes don't cover a column
+        assert_eq!(column_to_idx("a\u{200B}b", 1), 4);
+    }
+
+    #[test]
+    fn test_idx_to_column() {
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        assert_eq!(idx_to_column(st, 0), 0);
+        assert_eq!(idx_to_column(st, 5), 0);
+        assert_eq!(idx_to_column(st, 6), 2);
+        assert_eq!(idx_to_column(st, 17), 2);
+        assert_eq!(idx_to_column(st, 18), 5);
+        assert_eq!(idx_to_column(st, 19), 6);
+        assert_eq!(idx_to_column(st, 23), 8);
+        assert_eq!(idx_to_column(st, 46), 17);
+        assert_eq!(idx_to_column(st, 47), 18);
+        assert_eq!(idx_to_column(st, 999), 18);
+        assert_eq!(idx_to_column("", 3), 0);
+
+        // Round trips with column_to_idx on grapheme starts
+        for (idx, _) in st.grapheme_indices(true) {
+            assert_eq!(column_to_idx(st, idx_to_column(st, idx)), idx);
+        }
+    }
+
+    #[test]
+    fn test_is_single_grapheme() {
+        assert!(!is_single_grapheme(""));
+        assert!(is_single_grapheme("a"));
+        assert!(is_single_grapheme("\u{1F9D1}"));
+        assert!(is_single_grapheme("é"));
+        assert!(is_single_grapheme("\r\n"));
+        assert!(!is_single_grapheme("ab"));
+        assert!(!is_single_grapheme("हिन्दीH🧑🌾e‘︀o‘︁réé"));
+
+        // The README farmer halves are two graphemes, with a ZWJ it's one
+        assert!(!is_single_grapheme("\u{1F9D1}\u{1F33E}"));
+        assert!(is_single_grapheme("\u{1F9D1}\u{200D}\u{1F33E}"));
+    }
+
+    #[test]
+    fn test_equalize_widths() {
+        assert_eq!(equalize_widths("", ""), (String::new(), String::new()));
+        assert_eq!(
+            equalize_widths("hi", "你好"),
+            ("hi  ".to_string(), "你好".to_string())
+        );
+        assert_eq!(
+            equalize_widths("你好", "x"),
+            ("你好".to_string(), "x   ".to_string())
+        );
+        assert_eq!(
+            equalize_widths("ab", "cd"),
+            ("ab".to_string(), "cd".to_string())
+        );
+        let (a, b) = equalize_widths("हिन्दीH🧑🌾e‘︀o‘︁réé", "é");
+        assert_eq!(string_width(&a), 18);
+        assert_eq!(string_width(&b), 18);
+    }
+
+    #[test]
+    fn test_grapheme_map() {
+        let map = GraphemeMap::new("");
+        assert_eq!((map.len(), map.is_empty()), (0, true));
+        assert_eq!((map.nth(0), map.nth_idx(0)), ("", 0));
+
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        let map = GraphemeMap::new(st);
+        assert_eq!(map.len(), 12);
+        assert!(!map.is_empty());
+        for n in 0..15 {
+            assert_eq!(map.nth(n), nth_grapheme(st, n));
+            assert_eq!(map.nth_idx(n), nth_grapheme_idx(st, n));
+        }
+        assert_eq!(map.nth(1), "न्दी");
+        assert_eq!(map.nth_idx(12), 47);
+    }
+
+    #[test]
+    fn test_find_all_graphemes() {
+        assert_eq!(find_all_graphemes("", "a"), Vec::<usize>::new());
+        assert_eq!(find_all_graphemes("abc", ""), Vec::<usize>::new());
+        assert_eq!(find_all_graphemes("abcabc", "bc"), vec![1, 4]);
+        assert_eq!(find_all_graphemes("aaaa", "aa"), vec![0, 2]);
+
+        // Only the asked for form of é is found
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé \u{e9}é";
+        assert_eq!(find_all_graphemes(st, "\u{e9}"), vec![42, 48]);
+        assert_eq!(find_all_graphemes(st, "é"), vec![44, 50]);
+        assert_eq!(find_all_graphemes(st, "e"), vec![27]);
+
+        // Matches spanning several graphemes
+        assert_eq!(find_all_graphemes(st, "H\u{1F9D1}"), vec![18]);
+        assert_eq!(find_all_graphemes("你好你好你", "你好"), vec![0, 6]);
+    }
+
+    #[test]
+    fn test_graphemes_rev() {
+        assert_eq!(graphemes_rev("").count(), 0);
+        assert_eq!(
+            graphemes_rev("a你é").collect::<Vec<_>>(),
+            vec![(4, "é"), (1, "你"), (0, "a")]
+        );
+        let st = "हिन्दीH🧑🌾e‘︀o‘︁réé";
+        let mut forward: Vec<_> = st.grapheme_indices(true).collect();
+        forward.reverse();
+        assert_eq!(graphemes_rev(st).collect::<Vec<_>>(), forward);
+
+        // Trim trailing whitespace graphemes
+        let st = "abé \t ";
+        let end = graphemes_rev(st)
+            .find(|(_, g)| !g.trim().is_empty())
+            .map_or(0, |(idx, g)| idx + g.len());
+        assert_eq!(&st[..end], "abé");
+    }
+
+    #[test]
+    fn test_common_prefix_str() {
+        assert_eq!(common_prefix_str("", ""), "");
+        assert_eq!(common_prefix_str("abc", ""), "");
+        assert_eq!(common_prefix_str("abcdef", "abcXYZ"), "abc");
+        assert_eq!(common_prefix_str("abc", "abcdef"), "abc");
+        assert_eq!(common_prefix_str("xyz", "abc"), "");
+        assert_eq!(
+            common_prefix_str("हिन्दीH🧑🌾e‘︀o‘︁réé", "हिन्दीH🧑🌾e‘︀o‘︁réé"),
+            "हिन्दीH🧑🌾e‘︀o‘︁réé"
+        );
+        assert_eq!(common_prefix_str("हिन्दी", "हिन्"), "हि");
+
+        // Whole graphemes, e is not a prefix of the accented e
+        assert_eq!(common_prefix_str("ae", "aé"), "a");
+        assert_eq!(common_prefix_str("aéx", "aéy"), "aé");
+    }
+
+    #[test]
+    fn test_prev_grapheme_idx_from_idx_long() {
+        // Clusters and lookback much longer than one chunk
+        let marks = "e".to_string() + &"\u{301}".repeat(200);
+        let flags = "x".to_string() + &"🇺🇸".repeat(40) + "🇺";
+        let long = "a".repeat(1000) + &marks + "b" + &flags;
+        for st in [marks.as_str(), flags.as_str(), long.as_str()] {
+            let starts: Vec<usize> = st.grapheme_indices(true).map(|(i, _)| i).collect();
+            for idx in 0..=st.len() + 1 {
+                // Mid char idx values round up to the next char
+                let mut pos = idx.min(st.len());
+                while !st.is_char_boundary(pos) {
+                    pos += 1;
+                }
+                let expected = starts
+                    .iter()
+                    .rev()
+                    .find(|&&start| start < pos)
+                    .copied()
+                    .unwrap_or(0);
+                assert_eq!(prev_grapheme_idx_from_idx(st, idx), expected, "idx {}", idx);
+            }
+        }
+        assert_eq!(prev_grapheme_idx_from_idx(&marks, marks.len()), 0);
+        assert_eq!(
+            prev_grapheme_idx_from_idx(&flags, flags.len()),
+            flags.len() - 4
+        );
+    }
+
+    #[test]
+    fn test_padding_waste() {
+        assert_eq!(padding_waste(&[]), 0);
+        assert_eq!(padding_waste(&[""]), 0);
+        assert_eq!(padding_waste(&["abc", "xyz"]), 0);
+        assert_eq!(padding_waste(&["a", "你", "hello"]), 7);
+        assert_eq!(padding_waste(&["हिन्दीH🧑🌾e‘︀o‘︁réé", "", "H"]), 18 + 17);
     }
 }